@@ -0,0 +1,44 @@
+use std::collections::hash_map::DefaultHasher;
+use std::hash::{Hash, Hasher};
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+// Plugins talk to the app through plain Rust types, which only line up when both sides were compiled
+// by the same compiler from the same source with the same features. This fingerprints all three as
+// OVERDRAW_BUILD, a plugin gets the same string when it builds its copy of the crate
+fn main() {
+    let rustc = std::env::var("RUSTC").unwrap_or_else(|_| "rustc".to_string());
+    let version = Command::new(&rustc).arg("-vV").output()
+        .map(|output| String::from_utf8_lossy(&output.stdout).lines().next().unwrap_or_default().to_string())
+        .unwrap_or_default();
+
+    let mut features: Vec<String> = std::env::vars()
+        .filter_map(|(key, _)| key.strip_prefix("CARGO_FEATURE_").map(|feature| feature.to_lowercase()))
+        .collect();
+    features.sort();
+
+    let mut files = Vec::new();
+    collect_sources(Path::new("src"), &mut files);
+    files.sort();
+    let mut hasher = DefaultHasher::new();
+    for file in &files {
+        file.to_string_lossy().replace('\\', "/").hash(&mut hasher);
+        std::fs::read(file).unwrap_or_default().hash(&mut hasher);
+    }
+
+    println!("cargo:rustc-env=OVERDRAW_BUILD={} {} [{}] {:016x}", std::env::var("CARGO_PKG_VERSION").unwrap_or_default(), version, features.join(","), hasher.finish());
+    println!("cargo:rerun-if-changed=src");
+}
+
+fn collect_sources(dir: &Path, files: &mut Vec<PathBuf>) {
+    let Ok(entries) = std::fs::read_dir(dir) else {
+        return;
+    };
+    for path in entries.flatten().map(|entry| entry.path()) {
+        if path.is_dir() {
+            collect_sources(&path, files);
+        } else if path.extension().and_then(|extension| extension.to_str()) == Some("rs") {
+            files.push(path);
+        }
+    }
+}
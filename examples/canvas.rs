@@ -0,0 +1,51 @@
+// Draws on a Canvas without the overlay around it and writes the result as a PNG:
+// cargo run --example canvas -- out.png
+use std::path::PathBuf;
+use glm::*;
+use imgui_glfw_rs::glfw;
+use imgui_glfw_rs::glfw::{Context, OpenGlProfileHint, WindowHint, WindowMode};
+use overdraw::Canvas;
+
+fn main() {
+    let path = PathBuf::from(std::env::args().nth(1).unwrap_or_else(|| "canvas.png".to_string()));
+
+    // The canvas only needs a context, the window never shows up
+    let mut p_glfw = glfw::init(glfw::FAIL_ON_ERRORS).expect("[ERROR]: Failed to initialize GLFW!");
+    p_glfw.window_hint(WindowHint::Visible(false));
+    p_glfw.window_hint(WindowHint::ContextVersion(4, 6));
+    p_glfw.window_hint(WindowHint::OpenGlProfile(OpenGlProfileHint::Core));
+    p_glfw.window_hint(WindowHint::OpenGlForwardCompat(true));
+    let (mut p_window, _events) = p_glfw.create_window(1, 1, "Overdraw canvas", WindowMode::Windowed)
+        .expect("[ERROR]: Failed to create Window!");
+    p_window.make_current();
+    gl::load_with(|proc_addr| p_glfw.get_proc_address_raw(proc_addr));
+
+    let mut canvas = Canvas::new(400, 300);
+
+    // A spiral that stays
+    let center = vec2(200.0, 150.0);
+    canvas.begin_stroke(center, vec4(1.0, 0.3, 0.2, 1.0), 4);
+    for i in 1..200 {
+        let angle = i as f32 * 0.1;
+        canvas.add_point(center + vec2(angle.cos(), angle.sin()) * angle * 6.0);
+    }
+    canvas.end_stroke();
+
+    // A zigzag across it that gets undone, then redone and undone again
+    canvas.begin_stroke(vec2(20.0, 20.0), vec4(0.2, 0.4, 1.0, 1.0), 8);
+    for i in 1..10 {
+        canvas.add_point(vec2(20.0 + i as f32 * 40.0, if i % 2 == 0 { 20.0 } else { 280.0 }));
+    }
+    canvas.end_stroke();
+    canvas.undo();
+    canvas.redo();
+    canvas.undo();
+
+    match canvas.export(&path) {
+        Ok(()) => println!("wrote {}", path.display()),
+        Err(error) => {
+            eprintln!("overdraw: {}", error);
+            std::process::exit(1);
+        }
+    }
+}
@@ -0,0 +1,48 @@
+use glm::*;
+use serde::{Deserialize, Serialize};
+use crate::open_gl::shaders::StandardShader;
+
+// Keep in sync with background_paper() in PIXEL_SHADER
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum BackgroundPattern {
+    Plain,
+    Ruled,
+    Dotted,
+    Graph
+}
+
+// A board behind the ink, only exists in the screen pass so the canvas stays transparent
+pub struct Background {
+    pub enabled: bool,
+    pub color: Vec4,
+    pub pattern: BackgroundPattern,
+    pub spacing: i32,
+    pub pattern_color: Vec4
+}
+
+impl Background {
+    pub fn new() -> Self {
+        let mut background = Background { enabled: false, color: vec4(0.0, 0.0, 0.0, 0.0), pattern: BackgroundPattern::Plain, spacing: 32, pattern_color: vec4(0.0, 0.0, 0.0, 0.0) };
+        background.whiteboard();
+        background
+    }
+
+    pub fn whiteboard(&mut self) {
+        self.color = vec4(1.0, 1.0, 1.0, 1.0);
+        self.pattern_color = vec4(0.55, 0.7, 0.9, 0.6);
+    }
+
+    pub fn blackboard(&mut self) {
+        self.color = vec4(0.1, 0.12, 0.11, 1.0);
+        self.pattern_color = vec4(1.0, 1.0, 1.0, 0.15);
+    }
+
+    pub fn bind(&self, shader: &StandardShader) {
+        shader.set_int(self.enabled as i32, "background");
+        shader.set_vector4(&self.color, "background_color");
+        shader.set_int(self.pattern as i32, "background_pattern");
+        shader.set_int(Ord::max(self.spacing, 4), "background_spacing");
+        shader.set_vector4(&self.pattern_color, "background_pattern_color");
+    }
+}
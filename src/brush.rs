@@ -0,0 +1,64 @@
+use glm::*;
+use crate::font;
+use crate::open_gl::shaders::ComputeShader;
+use crate::symmetry::Symmetry;
+use crate::tiles::Surface;
+
+// Largest brush the size field and the resize gesture go up to, also what collaborators' strokes are held to
+pub const MAX_BRUSH_SIZE: i32 = 500;
+
+// A transparent color erases, which never needs new tiles
+pub fn paint(compute_shader: &ComputeShader, surface: &dyn Surface, color: Vec4, cursor_coords: Vec2, prev_cursor_coords: Vec2, brush_size: i32) {
+    compute_shader.set_ivector2(&cursor_coords, "cursor_pos");
+    compute_shader.set_ivector2(&prev_cursor_coords, "prev_cursor_pos");
+    compute_shader.set_int(brush_size, "brush_size");
+    compute_shader.set_vector4(&color, "color");
+
+    let reach = vec2(brush_size as f32, brush_size as f32);
+    let (min_corner, max_corner) = (min(cursor_coords, prev_cursor_coords) - reach, max(cursor_coords, prev_cursor_coords) + reach);
+    surface.regions(min_corner, max_corner, color.w > 0.0, &mut |region| region.dispatch(compute_shader));
+}
+
+pub fn paint_polyline(compute_shader: &ComputeShader, surface: &dyn Surface, color: Vec4, points: &[Vec2], brush_size: i32) {
+    for segment in points.windows(2) {
+        paint(compute_shader, surface, color, segment[1], segment[0], brush_size);
+    }
+}
+
+// Paints the segment and all of its mirrored copies
+pub fn paint_symmetric(compute_shader: &ComputeShader, surface: &dyn Surface, color: Vec4, cursor_coords: Vec2, prev_cursor_coords: Vec2, brush_size: i32, symmetry: &Symmetry) {
+    for (cursor, prev_cursor) in symmetry.apply(cursor_coords).into_iter().zip(symmetry.apply(prev_cursor_coords)) {
+        paint(compute_shader, surface, color, cursor, prev_cursor, brush_size);
+    }
+}
+
+pub fn paint_polyline_symmetric(compute_shader: &ComputeShader, surface: &dyn Surface, color: Vec4, points: &[Vec2], brush_size: i32, symmetry: &Symmetry) {
+    for segment in points.windows(2) {
+        paint_symmetric(compute_shader, surface, color, segment[1], segment[0], brush_size, symmetry);
+    }
+}
+
+// Composites tightly packed RGBA pixels over the canvas
+pub fn blend_pixels(surface: &dyn Surface, x: i32, y: i32, width: u32, height: u32, pixels: &[f32]) {
+    let mut region = surface.read(x, y, width, height);
+    for (dst, src) in region.chunks_exact_mut(4).zip(pixels.chunks_exact(4)) {
+        let blended = over(src, dst);
+        dst.copy_from_slice(&blended);
+    }
+    surface.write(x, y, width, height, &region);
+}
+
+// Draws text with its bottom left corner at the given position
+pub fn draw_text(surface: &dyn Surface, text: &str, pos: Vec2, scale: u32, color: Vec4) {
+    let (width, height, pixels) = font::rasterize(text, scale, [color.x, color.y, color.z, color.w]);
+    blend_pixels(surface, pos.x as i32, pos.y as i32, width, height, &pixels);
+}
+
+pub fn over(src: &[f32], dst: &[f32]) -> [f32; 4] {
+    let alpha = src[3] + dst[3] * (1.0 - src[3]);
+    if alpha <= 0.0 {
+        return [0.0; 4];
+    }
+    let channel = |i: usize| (src[i] * src[3] + dst[i] * dst[3] * (1.0 - src[3])) / alpha;
+    [channel(0), channel(1), channel(2), alpha]
+}
@@ -0,0 +1,211 @@
+use device_query::Keycode;
+use glm::*;
+use crate::brush::*;
+use crate::font;
+use crate::history::History;
+use crate::input::Input;
+use crate::tiles::Surface;
+
+const PADDING: f32 = 8.0;
+const MIN_HALF_SIZE: Vec2 = Vec2 { x: 30.0, y: 12.0 };
+const CORNER_RADIUS: f32 = 8.0;
+const TAIL_WIDTH: f32 = 16.0;
+const BORDER_WIDTH: f32 = 2.0;
+// How close a click has to be to the tail's tip to grab it
+const HANDLE_SIZE: f32 = 8.0;
+
+enum CalloutDrag {
+    Target,
+    Box(Vec2)
+}
+
+// A text box with a tail pointing at something, it stays on the overlay and editable until it's committed
+pub struct Callout {
+    pub text: String,
+    pub background: Vec4,
+    pub border: Vec4,
+    pub text_color: Vec4,
+    pub text_scale: i32,
+    start: Option<Vec2>,
+    end: Vec2,
+    placed: Option<(Vec2, Vec2)>,
+    drag: Option<CalloutDrag>
+}
+
+impl Callout {
+    pub fn new() -> Self {
+        Callout {
+            text: String::new(),
+            background: vec4(1.0, 0.93, 0.55, 1.0),
+            border: vec4(0.2, 0.2, 0.2, 1.0),
+            text_color: vec4(0.0, 0.0, 0.0, 1.0),
+            text_scale: 2,
+            start: None,
+            end: vec2(0.0, 0.0),
+            placed: None,
+            drag: None
+        }
+    }
+
+    pub fn is_placed(&self) -> bool {
+        self.placed.is_some()
+    }
+
+    // Drag from the target to where the box goes, afterwards the tip and the box can be dragged around
+    pub fn update(&mut self, input: &Input, cursor: Vec2, history: &mut History) {
+        if input.get_key_down(Keycode::Escape) {
+            self.cancel();
+            return;
+        }
+
+        if input.get_button_down(1) {
+            if let Some((target, center)) = self.placed {
+                let (min, max) = self.bounds(center);
+                self.drag = if length(cursor - target) < HANDLE_SIZE {
+                    Some(CalloutDrag::Target)
+                } else if cursor.x >= min.x && cursor.x <= max.x && cursor.y >= min.y && cursor.y <= max.y {
+                    Some(CalloutDrag::Box(cursor - center))
+                } else {
+                    None
+                };
+                // Clicking somewhere else finishes this callout and starts the next one
+                if self.drag.is_none() {
+                    self.commit(history);
+                    self.start = Some(cursor);
+                }
+            } else {
+                self.start = Some(cursor);
+            }
+        }
+        self.end = cursor;
+
+        if let Some((target, center)) = self.placed.as_mut() {
+            match self.drag {
+                Some(CalloutDrag::Target) => *target = cursor,
+                Some(CalloutDrag::Box(grab)) => *center = cursor - grab,
+                None => {}
+            }
+        }
+
+        if !input.get_button(1) {
+            self.drag = None;
+            if let Some(start) = self.start.take() {
+                self.placed = Some((start, self.box_center(start, cursor)));
+            }
+        }
+    }
+
+    // Draws the callout onto the canvas as a single history step
+    pub fn commit(&mut self, history: &mut History) {
+        if let Some((target, center)) = self.placed.take() {
+            history.begin_step();
+            self.render(history.get_current(), target, center);
+            self.text.clear();
+        }
+        self.drag = None;
+    }
+
+    pub fn cancel(&mut self) {
+        self.start = None;
+        self.placed = None;
+        self.drag = None;
+    }
+
+    pub fn preview(&self, surface: &dyn Surface) {
+        if let Some(start) = self.start {
+            self.render(surface, start, self.box_center(start, self.end));
+        } else if let Some((target, center)) = self.placed {
+            self.render(surface, target, center);
+        }
+    }
+
+    // Without a real drag the box is put next to the target instead of on top of it
+    fn box_center(&self, target: Vec2, end: Vec2) -> Vec2 {
+        if length(end - target) < HANDLE_SIZE {
+            let half_size = self.half_size();
+            target + vec2(half_size.x, half_size.y) + vec2(TAIL_WIDTH, TAIL_WIDTH)
+        } else {
+            end
+        }
+    }
+
+    // The box grows with its text
+    fn half_size(&self) -> Vec2 {
+        let scale = Ord::max(self.text_scale, 1) as u32;
+        let (width, height) = font::measure(&self.text, scale);
+        let half_size = vec2(width as f32, height as f32) * 0.5 + vec2(PADDING, PADDING);
+        vec2(half_size.x.max(MIN_HALF_SIZE.x), half_size.y.max(MIN_HALF_SIZE.y))
+    }
+
+    fn bounds(&self, center: Vec2) -> (Vec2, Vec2) {
+        let half_size = self.half_size();
+        (center - half_size, center + half_size)
+    }
+
+    fn render(&self, surface: &dyn Surface, target: Vec2, center: Vec2) {
+        let half_size = self.half_size();
+        let (min, max) = self.bounds(center);
+        let radius = CORNER_RADIUS.min(half_size.x).min(half_size.y);
+
+        // The tail starts in the middle of the box, the part inside of it is covered anyway
+        let dir = target - center;
+        let tail = if length(dir) > 0.0 {
+            let side = normalize(vec2(-dir.y, dir.x)) * TAIL_WIDTH.min(half_size.x).min(half_size.y) * 0.5;
+            Some((target, center + side, center - side))
+        } else {
+            None
+        };
+
+        let x0 = (min.x.min(target.x) - 2.0).floor() as i32;
+        let y0 = (min.y.min(target.y) - 2.0).floor() as i32;
+        let x1 = (max.x.max(target.x) + 2.0).ceil() as i32;
+        let y1 = (max.y.max(target.y) + 2.0).ceil() as i32;
+        let (width, height) = ((x1 - x0) as u32, (y1 - y0) as u32);
+
+        let mut pixels = vec![0.0; (width * height * 4) as usize];
+        for y in 0..height {
+            for x in 0..width {
+                let p = vec2(x0 as f32 + x as f32 + 0.5, y0 as f32 + y as f32 + 0.5);
+                let mut d = rounded_box_distance(p - center, half_size, radius);
+                if let Some((a, b, c)) = tail {
+                    d = d.min(triangle_distance(p, a, b, c));
+                }
+
+                let coverage = (0.5 - d).clamp(0.0, 1.0);
+                if coverage <= 0.0 {
+                    continue;
+                }
+                let color = mix(self.background, self.border, vec4(1.0, 1.0, 1.0, 1.0) * (d + BORDER_WIDTH + 0.5).clamp(0.0, 1.0));
+                let i = ((y * width + x) * 4) as usize;
+                pixels[i..i + 4].copy_from_slice(&[color.x, color.y, color.z, color.w * coverage]);
+            }
+        }
+        blend_pixels(surface, x0, y0, width, height, &pixels);
+
+        let scale = Ord::max(self.text_scale, 1) as u32;
+        let (text_width, text_height) = font::measure(&self.text, scale);
+        // The measured size includes a spacing column on the right and a row at the bottom
+        let pos = center - vec2(text_width as f32 - scale as f32, text_height as f32 + scale as f32) * 0.5;
+        draw_text(surface, &self.text, pos, scale, self.text_color);
+    }
+}
+
+fn rounded_box_distance(p: Vec2, half_size: Vec2, radius: f32) -> f32 {
+    let q = abs(p) - half_size + vec2(radius, radius);
+    length(vec2(q.x.max(0.0), q.y.max(0.0))) + q.x.max(q.y).min(0.0) - radius
+}
+
+fn triangle_distance(p: Vec2, a: Vec2, b: Vec2, c: Vec2) -> f32 {
+    let edges = [(a, b - a), (b, c - b), (c, a - c)];
+    let winding = ((b - a).x * (a - c).y - (b - a).y * (a - c).x).signum();
+
+    let mut distance = f32::MAX;
+    let mut side = f32::MAX;
+    for (start, edge) in edges {
+        let v = p - start;
+        let closest = v - edge * (dot(v, edge) / dot(edge, edge)).clamp(0.0, 1.0);
+        distance = distance.min(dot(closest, closest));
+        side = side.min(winding * (v.x * edge.y - v.y * edge.x));
+    }
+    -distance.sqrt() * side.signum()
+}
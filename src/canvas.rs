@@ -0,0 +1,91 @@
+use std::path::Path;
+use glm::*;
+use crate::brush::paint;
+use crate::history::History;
+use crate::image::save_png;
+use crate::open_gl::shaders::ComputeShader;
+use crate::shader_code::COMPUTE_SHADER;
+use crate::tiles::{Surface, Tiles};
+
+struct Stroke {
+    color: Vec4,
+    size: i32,
+    last: Vec2
+}
+
+// A single drawing surface with undo, for embedding the brush without the overlay around it.
+// Needs a current OpenGL 4.6 context, points are texels with the origin at the bottom left.
+// Strokes may go past the size, it's only what gets exported
+pub struct Canvas {
+    history: History,
+    width: u32,
+    height: u32,
+    compute_shader: ComputeShader,
+    stroke: Option<Stroke>
+}
+
+impl Canvas {
+    pub fn new(width: u32, height: u32) -> Self {
+        let history = History::new(Tiles::new());
+        Canvas { history, width, height, compute_shader: ComputeShader::new(COMPUTE_SHADER), stroke: None }
+    }
+
+    // Every stroke is one undo step, a stroke that's still going is ended first
+    pub fn begin_stroke(&mut self, point: Vec2, color: Vec4, size: i32) {
+        self.end_stroke();
+        self.history.begin_step();
+        paint(&self.compute_shader, self.history.get_current(), color, point, point, size);
+        self.stroke = Some(Stroke { color, size, last: point });
+    }
+
+    pub fn add_point(&mut self, point: Vec2) {
+        let Some(stroke) = &mut self.stroke else {
+            return;
+        };
+        paint(&self.compute_shader, self.history.get_current(), stroke.color, point, stroke.last, stroke.size);
+        stroke.last = point;
+    }
+
+    pub fn end_stroke(&mut self) {
+        self.stroke = None;
+    }
+
+    pub fn is_stroking(&self) -> bool {
+        self.stroke.is_some()
+    }
+
+    pub fn undo(&mut self) {
+        self.end_stroke();
+        self.history.undo();
+    }
+
+    pub fn redo(&mut self) {
+        self.end_stroke();
+        self.history.redo();
+    }
+
+    // Writes what the canvas shows right now as an 8 bit PNG
+    pub fn export(&self, path: &Path) -> Result<(), String> {
+        let pixels = self.tiles().read(0, 0, self.width, self.height);
+        save_png(path, self.width, self.height, &pixels)
+    }
+
+    // For drawing the canvas with the front end's own shader, see Tiles::render
+    pub fn tiles(&self) -> &Tiles {
+        self.history.get_current()
+    }
+
+    pub fn get_size(&self) -> (u32, u32) {
+        (self.width, self.height)
+    }
+
+    // The other tools work on the history directly
+    pub fn history(&self) -> &History {
+        &self.history
+    }
+
+    pub fn history_mut(&mut self) -> &mut History {
+        self.end_stroke();
+        &mut self.history
+    }
+}
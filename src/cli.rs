@@ -0,0 +1,187 @@
+use std::path::PathBuf;
+use glm::{vec4, Vec4};
+use crate::brush::MAX_BRUSH_SIZE;
+
+pub const USAGE: &str = "\
+Usage: overdraw [OPTIONS] [FILE]
+       overdraw ctl <COMMAND> [ARGS]
+
+Draws on a transparent window over the desktop.
+
+Arguments:
+  [FILE]               Same as --open
+
+Commands:
+  ctl                  Control the running overdraw, see 'overdraw ctl --help'
+
+Options:
+      --visible        Start in edit mode instead of waiting for the toggle shortcut
+      --hidden         Start hidden until the toggle shortcut is pressed (default)
+      --color <COLOR>  Initial brush color as #RRGGBB or #RRGGBBAA
+      --brush-size <PX>
+                       Initial brush size in pixels, 1 to 500
+      --open <FILE>    Load a PNG or .overdraw project onto the canvas
+      --monitor <N>    Monitor to cover, counted from 1 (default: the primary one)
+      --config <DIR>   Directory for settings, keybindings and stamps
+      --autosave <DIR> Save all pages to DIR/autosave.overdraw every minute and on exit
+      --host <ADDR>    Draw together with others who join at ADDR. A bare port like 7171 only
+                       listens on this machine, let others in with an address on their network
+                       like 0.0.0.0:7171 and a --secret
+      --join <ADDR>    Join the session hosted at ADDR, the port defaults to 7171
+      --secret <WORD>  Guests need the same secret as the host to join
+      --name <NAME>    Shown next to your cursor on the others' screens (default: user name)
+  -h, --help           Print this help
+  -V, --version        Print the version
+
+Two instances on one machine can draw together with --host 7171 and --join 127.0.0.1,
+give the second one its own --config so they don't share settings.
+";
+
+pub enum Command {
+    Run(Options),
+    Ctl(Vec<String>),
+    Help,
+    Version
+}
+
+#[derive(Default)]
+pub struct Options {
+    pub visible: bool,
+    pub color: Option<Vec4>,
+    pub brush_size: Option<i32>,
+    pub open: Option<PathBuf>,
+    pub monitor: Option<usize>,
+    pub config: Option<PathBuf>,
+    pub autosave: Option<PathBuf>,
+    pub session: Option<SessionRole>,
+    pub name: Option<String>,
+    pub secret: Option<String>
+}
+
+pub enum SessionRole {
+    Host(String),
+    Join(String)
+}
+
+// Accepts both `--flag value` and `--flag=value`, errors name the flag and the value that was rejected
+pub fn parse(args: impl IntoIterator<Item = String>) -> Result<Command, String> {
+    let mut options = Options::default();
+    let mut args = args.into_iter().peekable();
+    if args.peek().is_some_and(|arg| arg == "ctl") {
+        return Ok(Command::Ctl(args.skip(1).collect()));
+    }
+    while let Some(arg) = args.next() {
+        let (flag, inline) = match arg.split_once('=') {
+            Some((flag, value)) if flag.starts_with("--") => (flag.to_string(), Some(value.to_string())),
+            _ => (arg.clone(), None)
+        };
+        let mut value = || inline.clone().or_else(|| args.next()).ok_or(format!("{} needs a value", flag));
+
+        match flag.as_str() {
+            "-h" | "--help" => return Ok(Command::Help),
+            "-V" | "--version" => return Ok(Command::Version),
+            "--visible" | "--hidden" if inline.is_some() => return Err(format!("{} doesn't take a value", flag)),
+            "--visible" => options.visible = true,
+            "--hidden" => options.visible = false,
+            "--color" => options.color = Some(parse_color(&value()?)?),
+            "--brush-size" => {
+                let size = value()?;
+                options.brush_size = Some(size.parse().ok().filter(|size| (1..=MAX_BRUSH_SIZE).contains(size))
+                    .ok_or(format!("invalid brush size '{}', expected a whole number of pixels from 1 to {}", size, MAX_BRUSH_SIZE))?);
+            }
+            "--open" if options.open.is_some() => return Err("--open can't be repeated, only one file can be opened".to_string()),
+            "--open" => options.open = Some(PathBuf::from(value()?)),
+            "--monitor" => {
+                let monitor = value()?;
+                options.monitor = Some(monitor.parse().ok().filter(|monitor| *monitor >= 1)
+                    .ok_or(format!("invalid monitor '{}', expected a number counted from 1", monitor))?);
+            }
+            "--config" => options.config = Some(PathBuf::from(value()?)),
+            "--autosave" => options.autosave = Some(PathBuf::from(value()?)),
+            "--host" | "--join" if options.session.is_some() => return Err("--host and --join can't be combined or repeated".to_string()),
+            "--host" => options.session = Some(SessionRole::Host(value()?)),
+            "--join" => options.session = Some(SessionRole::Join(value()?)),
+            "--name" => {
+                let name = value()?;
+                if name.trim().is_empty() {
+                    return Err("--name can't be empty".to_string());
+                }
+                options.name = Some(name);
+            }
+            "--secret" => options.secret = Some(value()?),
+            _ if flag.starts_with('-') && flag != "-" => return Err(format!("unknown option '{}'", flag)),
+            _ if options.open.is_none() => options.open = Some(PathBuf::from(arg)),
+            _ => return Err(format!("unexpected argument '{}', only one file can be opened", arg))
+        }
+    }
+    Ok(Command::Run(options))
+}
+
+pub fn parse_color(text: &str) -> Result<Vec4, String> {
+    let hex = text.strip_prefix('#').unwrap_or(text);
+    if !(hex.len() == 6 || hex.len() == 8) || !hex.chars().all(|c| c.is_ascii_hexdigit()) {
+        return Err(format!("invalid color '{}', expected #RRGGBB or #RRGGBBAA", text));
+    }
+    // Validated above, so every pair parses, alpha is opaque unless given
+    let channel = |i: usize| hex.get(i * 2..i * 2 + 2).map_or(255, |pair| u8::from_str_radix(pair, 16).unwrap_or(0)) as f32 / 255.0;
+    Ok(vec4(channel(0), channel(1), channel(2), channel(3)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn run(args: &[&str]) -> Result<Options, String> {
+        match parse(args.iter().map(|arg| arg.to_string()))? {
+            Command::Run(options) => Ok(options),
+            _ => Err("not a run".to_string())
+        }
+    }
+
+    #[test]
+    fn parses_flags_with_and_without_equals() {
+        let options = run(&["--visible", "--brush-size=12", "--color", "#ff000080", "--monitor", "2", "board.png"]).unwrap();
+        assert!(options.visible);
+        assert_eq!(options.brush_size, Some(12));
+        assert_eq!(options.color, Some(vec4(1.0, 0.0, 0.0, 128.0 / 255.0)));
+        assert_eq!(options.monitor, Some(2));
+        assert_eq!(options.open, Some(PathBuf::from("board.png")));
+        assert!(matches!(run(&["--host", "7171"]).unwrap().session, Some(SessionRole::Host(address)) if address == "7171"));
+    }
+
+    #[test]
+    fn commands_come_first() {
+        assert!(matches!(parse(["ctl".to_string(), "undo".to_string()]), Ok(Command::Ctl(args)) if args == ["undo"]));
+        assert!(matches!(parse(["--visible".to_string(), "-h".to_string()]), Ok(Command::Help)));
+        assert!(matches!(parse(["-V".to_string()]), Ok(Command::Version)));
+    }
+
+    #[test]
+    fn rejects_bad_values() {
+        assert_eq!(run(&["--brush-size", "0"]).err().unwrap(), format!("invalid brush size '0', expected a whole number of pixels from 1 to {}", MAX_BRUSH_SIZE));
+        assert_eq!(run(&["--monitor=0"]).err().unwrap(), "invalid monitor '0', expected a number counted from 1");
+        assert_eq!(run(&["--color"]).err().unwrap(), "--color needs a value");
+        assert_eq!(run(&["--visible=yes"]).err().unwrap(), "--visible doesn't take a value");
+        assert_eq!(run(&["--name", " "]).err().unwrap(), "--name can't be empty");
+        assert_eq!(run(&["--frobnicate"]).err().unwrap(), "unknown option '--frobnicate'");
+    }
+
+    #[test]
+    fn rejects_repeats() {
+        assert_eq!(run(&["--open", "a.png", "--open", "b.png"]).err().unwrap(), "--open can't be repeated, only one file can be opened");
+        assert_eq!(run(&["a.png", "--open=b.png"]).err().unwrap(), "--open can't be repeated, only one file can be opened");
+        assert_eq!(run(&["a.png", "b.png"]).err().unwrap(), "unexpected argument 'b.png', only one file can be opened");
+        assert_eq!(run(&["--host", "7171", "--join", "127.0.0.1"]).err().unwrap(), "--host and --join can't be combined or repeated");
+    }
+
+    #[test]
+    fn parses_colors() {
+        assert_eq!(parse_color("#00ff00"), Ok(vec4(0.0, 1.0, 0.0, 1.0)));
+        assert_eq!(parse_color("0000FF00"), Ok(vec4(0.0, 0.0, 1.0, 0.0)));
+        assert!(parse_color("#0f0").is_err());
+        assert!(parse_color("#gg0000").is_err());
+        assert!(parse_color("#ff00001").is_err());
+        assert_eq!(parse_color("red").err().unwrap(), "invalid color 'red', expected #RRGGBB or #RRGGBBAA");
+    }
+}
+
@@ -0,0 +1,370 @@
+mod wire;
+
+use std::collections::{BTreeMap, VecDeque};
+use std::time::{Duration, Instant};
+use base64::Engine;
+use base64::engine::general_purpose::STANDARD as BASE64;
+use glm::*;
+use crate::brush::{draw_text, paint, paint_polyline};
+use crate::font;
+use crate::history::differs;
+use crate::image::{decode_png, encode_png};
+use crate::open_gl::shaders::ComputeShader;
+use crate::pages::Pages;
+use crate::tiles::{tile_origin, Surface, Tiles, TILE_SIZE};
+use crate::tools::StrokeEvent;
+use self::wire::{Link, Message};
+
+const CURSOR_RADIUS: f32 = 6.0;
+const NAME_SCALE: u32 = 2;
+// How often the current page is compared with what the others were told, comparing waits for the GPU
+const SYNC_INTERVAL: Duration = Duration::from_millis(250);
+
+// What a stroke painted since it began or last reverted, in wire coordinates
+struct Stroke {
+    page: usize,
+    events: Vec<StrokeEvent>
+}
+
+// Someone else, and the stroke they're in the middle of
+struct Peer {
+    name: String,
+    cursor: Option<[f32; 2]>,
+    color: Vec4,
+    stroke: Option<Stroke>
+}
+
+// Something the others finished, waiting until nothing is being drawn here
+enum Change {
+    Stroke(Stroke),
+    // Bottom row first, with the top left corner at the position in wire coordinates
+    Pixels { page: usize, position: [i32; 2], width: u32, height: u32, pixels: Vec<f32> }
+}
+
+// The current page as the others were last told about it
+struct Shadow {
+    page: usize,
+    pages: usize,
+    tiles: Tiles
+}
+
+// A drawing session shared with other instances over TCP, polled once per frame like the control socket.
+// Strokes from tools go out as they're drawn and show over the others' canvases, once one ends it becomes
+// an undo step of its own on every canvas. Everything else that changes the current page, undo and redo
+// included, goes out as the tiles it changed, so the canvases end up with the same pixels. Those tiles are a
+// new step for the others, an undo here is a forward step there and their own undo brings the stroke back
+// for everyone. Changes to pages that aren't shown, like from the control socket, stay local
+pub struct Session {
+    link: Link,
+    peers: BTreeMap<u32, Peer>,
+    queue: VecDeque<Change>,
+    // The stroke going on here, and the ones that ended since the shadow was last brought up to date
+    stroke: Option<Stroke>,
+    ended: Vec<Stroke>,
+    shadow: Option<Shadow>,
+    synced_at: Instant,
+    // The height of the screen the canvas started on, wire coordinates count from its top
+    screen_height: f32,
+    sent_cursor: Option<([f32; 2], [f32; 4])>
+}
+
+impl Session {
+    pub fn host(address: &str, name: &str, secret: &str, screen_height: f32) -> Result<Self, String> {
+        Ok(Session::new(Link::host(address, name, secret)?, screen_height))
+    }
+
+    pub fn join(address: &str, name: &str, secret: &str, screen_height: f32) -> Result<Self, String> {
+        Ok(Session::new(Link::join(address, name, secret)?, screen_height))
+    }
+
+    fn new(link: Link, screen_height: f32) -> Self {
+        Session {
+            link,
+            peers: BTreeMap::new(),
+            queue: VecDeque::new(),
+            stroke: None,
+            ended: Vec::new(),
+            shadow: None,
+            synced_at: Instant::now(),
+            screen_height,
+            sent_cursor: None
+        }
+    }
+
+    // Takes in what the others did since the last call. While something is being drawn here their finished
+    // strokes wait, so they never end up inside a local undo step. Fails once a guest lost its host
+    pub fn poll(&mut self, pages: &mut Pages, compute_shader: &ComputeShader, busy: bool) -> Result<(), String> {
+        let messages = self.link.poll().inspect_err(|_| self.peers.clear())?;
+        for message in messages {
+            self.receive(message);
+        }
+        if busy {
+            return Ok(());
+        }
+        self.sync(pages, compute_shader);
+        while let Some(change) = self.queue.pop_front() {
+            self.apply(change, pages, compute_shader);
+        }
+        Ok(())
+    }
+
+    fn receive(&mut self, message: Message) {
+        match message {
+            Message::Joined { peer, name } => {
+                self.peers.insert(peer, Peer { name, cursor: None, color: vec4(1.0, 1.0, 1.0, 1.0), stroke: None });
+            }
+            Message::Left { peer } => {
+                // Whatever they were drawing stays
+                if let Some(stroke) = self.peers.remove(&peer).and_then(|peer| peer.stroke) {
+                    self.queue.push_back(Change::Stroke(stroke));
+                }
+            }
+            Message::Cursor { peer, position, color } => {
+                if let Some(peer) = self.peers.get_mut(&peer) {
+                    peer.cursor = position;
+                    peer.color = vec4(color[0], color[1], color[2], color[3]);
+                }
+            }
+            Message::Begin { peer, page, .. } => {
+                let Some(peer) = self.peers.get_mut(&peer) else {
+                    return;
+                };
+                if let Some(stroke) = peer.stroke.replace(Stroke { page, events: Vec::new() }) {
+                    self.queue.push_back(Change::Stroke(stroke));
+                }
+            }
+            Message::Paint { peer, page, color, from, to, size } => {
+                let color = vec4(color[0], color[1], color[2], color[3]);
+                if let Some(stroke) = self.stroke_of(peer, page) {
+                    stroke.events.push(StrokeEvent::Paint { color, from: vec2(from[0], from[1]), to: vec2(to[0], to[1]), size });
+                }
+            }
+            Message::Revert { peer, page } => {
+                if let Some(stroke) = self.stroke_of(peer, page) {
+                    stroke.events.clear();
+                }
+            }
+            Message::Clear { peer, page } => {
+                if let Some(stroke) = self.stroke_of(peer, page) {
+                    stroke.events.push(StrokeEvent::Clear);
+                }
+            }
+            Message::End { peer, page } => {
+                if self.stroke_of(peer, page).is_some() {
+                    if let Some(stroke) = self.peers.get_mut(&peer).and_then(|peer| peer.stroke.take()) {
+                        self.queue.push_back(Change::Stroke(stroke));
+                    }
+                }
+            }
+            Message::Pixels { peer, page, position, png } => {
+                // The size is checked before decoding, so a PNG that claims to be huge isn't
+                let decoded = BASE64.decode(png).map_err(|error| error.to_string()).and_then(|png| match png_size(&png) {
+                    Some((width, height)) if width <= TILE_SIZE && height <= TILE_SIZE => decode_png(png.as_slice()),
+                    _ => Err("pixels that aren't a PNG of at most a tile".to_string())
+                });
+                match decoded {
+                    Ok((width, height, pixels)) => self.queue.push_back(Change::Pixels { page, position, width, height, pixels }),
+                    Err(error) => eprintln!("[ERROR::COLLAB::RECEIVE] peer {}: {}", peer, error)
+                }
+            }
+            Message::Hello { .. } | Message::Welcome { .. } | Message::Refused { .. } => {}
+        }
+    }
+
+    // Events for a stroke that never began, or began on another page, are dropped
+    fn stroke_of(&mut self, peer: u32, page: usize) -> Option<&mut Stroke> {
+        self.peers.get_mut(&peer)?.stroke.as_mut().filter(|stroke| stroke.page == page)
+    }
+
+    // Sends the tiles of the current page that changed since the others were last told, other than by strokes
+    fn sync(&mut self, pages: &Pages, compute_shader: &ComputeShader) {
+        let (page, count) = (pages.current_index(), pages.len());
+        let current = pages.current().get_current();
+        let screen_height = self.screen_height;
+        let ended = std::mem::take(&mut self.ended);
+        let shadow = match &mut self.shadow {
+            Some(shadow) if shadow.page == page && shadow.pages == count => shadow,
+            // On another page, or after pages came or went, what's there is taken to be what the others have
+            _ => {
+                self.shadow = Some(Shadow { page, pages: count, tiles: current.duplicate() });
+                return;
+            }
+        };
+        // Local strokes already went out as they were drawn
+        for stroke in ended.iter().filter(|stroke| stroke.page == page) {
+            for event in &stroke.events {
+                draw_event(&map_points(*event, |p| to_canvas(p, screen_height)), &shadow.tiles, compute_shader);
+            }
+        }
+
+        if self.synced_at.elapsed() < SYNC_INTERVAL {
+            return;
+        }
+        self.synced_at = Instant::now();
+        let mut keys = current.keys();
+        keys.extend(shadow.tiles.keys().into_iter().filter(|key| current.get(*key).is_none()));
+        let changed = {
+            let tiles: Vec<_> = keys.iter().map(|key| (shadow.tiles.get(*key), current.get(*key))).collect();
+            differs(&tiles.iter().map(|(before, after)| (before.as_deref(), after.as_deref())).collect::<Vec<_>>())
+        };
+        // A tile that went away goes out blank
+        for key in keys.into_iter().zip(changed).filter(|(_, changed)| *changed).map(|(key, _)| key) {
+            let corner = tile_origin(key);
+            let pixels = current.read(corner.x as i32, corner.y as i32, TILE_SIZE, TILE_SIZE);
+            let mut png = Vec::new();
+            if let Err(error) = encode_png(&mut png, TILE_SIZE, TILE_SIZE, &pixels) {
+                eprintln!("[ERROR::COLLAB::SYNC] {}", error);
+                continue;
+            }
+            shadow.tiles.replace(key, current.get(key).map(|tile| tile.clone()));
+            let corner = to_wire(corner + vec2(0.0, TILE_SIZE as f32), screen_height);
+            let position = [corner.x.round() as i32, corner.y.round() as i32];
+            self.link.send(Message::Pixels { peer: self.link.id(), page, position, png: BASE64.encode(png) });
+        }
+    }
+
+    // Into the page's history as an undo step of its own, and into the shadow so it isn't sent back
+    fn apply(&mut self, change: Change, pages: &mut Pages, compute_shader: &ComputeShader) {
+        let screen_height = self.screen_height;
+        match change {
+            Change::Stroke(stroke) => {
+                // Pages only one side has are left alone, and a stroke that painted nothing is no undo step
+                let Some(history) = pages.get_mut(stroke.page).filter(|_| !stroke.events.is_empty()) else {
+                    return;
+                };
+                let shadow = self.shadow.as_ref().filter(|shadow| shadow.page == stroke.page);
+                history.begin_step();
+                for event in &stroke.events {
+                    let event = map_points(*event, |p| to_canvas(p, screen_height));
+                    event.apply(history, compute_shader);
+                    if let Some(shadow) = shadow {
+                        draw_event(&event, &shadow.tiles, compute_shader);
+                    }
+                }
+            }
+            Change::Pixels { page, position, width, height, pixels } => {
+                let Some(history) = pages.get_mut(page) else {
+                    return;
+                };
+                let corner = to_canvas(vec2(position[0] as f32, position[1] as f32), screen_height);
+                let (x, y) = (corner.x.round() as i32, corner.y.round() as i32 - height as i32);
+                history.begin_step();
+                history.get_current().write(x, y, width, height, &pixels);
+                if let Some(shadow) = self.shadow.as_ref().filter(|shadow| shadow.page == page) {
+                    shadow.tiles.write(x, y, width, height, &pixels);
+                }
+            }
+        }
+    }
+
+    // What a local tool did this frame on the page with that index, active while its stroke is still going
+    pub fn send_stroke(&mut self, page: usize, tool: &str, events: &[StrokeEvent], active: bool) {
+        let (peer, screen_height) = (self.link.id(), self.screen_height);
+        for event in events {
+            let event = map_points(*event, |p| to_wire(p, screen_height));
+            let message = match event {
+                StrokeEvent::Begin => {
+                    self.end_stroke();
+                    self.stroke = Some(Stroke { page, events: Vec::new() });
+                    Message::Begin { peer, page, tool: tool.to_string() }
+                }
+                StrokeEvent::Paint { color, from, to, size } => {
+                    Message::Paint { peer, page, color: *color.as_array(), from: *from.as_array(), to: *to.as_array(), size }
+                }
+                StrokeEvent::Revert => Message::Revert { peer, page },
+                StrokeEvent::Clear => Message::Clear { peer, page }
+            };
+            if let Some(stroke) = &mut self.stroke {
+                match event {
+                    StrokeEvent::Revert => stroke.events.clear(),
+                    StrokeEvent::Paint { .. } | StrokeEvent::Clear => stroke.events.push(event),
+                    StrokeEvent::Begin => {}
+                }
+            }
+            self.link.send(message);
+        }
+        if !active {
+            self.end_stroke();
+        }
+    }
+
+    fn end_stroke(&mut self) {
+        if let Some(stroke) = self.stroke.take() {
+            self.link.send(Message::End { peer: self.link.id(), page: stroke.page });
+            self.ended.push(stroke);
+        }
+    }
+
+    // Only goes out when it moved or changed color, None hides it on the other screens
+    pub fn send_cursor(&mut self, cursor: Option<Vec2>, color: Vec4) {
+        let current = cursor.map(|cursor| (*to_wire(cursor, self.screen_height).as_array(), *color.as_array()));
+        if current == self.sent_cursor {
+            return;
+        }
+        self.sent_cursor = current;
+        let position = current.map(|(position, _)| position);
+        self.link.send(Message::Cursor { peer: self.link.id(), position, color: *color.as_array() });
+    }
+
+    // The strokes the others are drawing on the page, then a ring in each one's brush color with their name next to it
+    pub fn draw_peers(&self, overlay: &dyn Surface, compute_shader: &ComputeShader, page: usize) {
+        let waiting = self.queue.iter().filter_map(|change| match change {
+            Change::Stroke(stroke) => Some(stroke),
+            Change::Pixels { .. } => None
+        });
+        for stroke in self.peers.values().filter_map(|peer| peer.stroke.as_ref()).chain(waiting).filter(|stroke| stroke.page == page) {
+            for event in &stroke.events {
+                // Clearing the overlay would take everything else on it along
+                if let StrokeEvent::Paint { .. } = event {
+                    draw_event(&map_points(*event, |p| to_canvas(p, self.screen_height)), overlay, compute_shader);
+                }
+            }
+        }
+
+        for peer in self.peers.values() {
+            let Some(position) = peer.cursor else {
+                continue;
+            };
+            let center = to_canvas(vec2(position[0], position[1]), self.screen_height);
+            let ring: Vec<Vec2> = (0..=16).map(|i| {
+                let angle = i as f32 / 16.0 * std::f32::consts::TAU;
+                center + vec2(angle.cos(), angle.sin()) * CURSOR_RADIUS
+            }).collect();
+            paint_polyline(compute_shader, overlay, peer.color, &ring, 1);
+            let (_, height) = font::measure(&peer.name, NAME_SCALE);
+            draw_text(overlay, &peer.name, center + vec2(CURSOR_RADIUS + 4.0, -CURSOR_RADIUS - height as f32), NAME_SCALE, peer.color);
+        }
+    }
+}
+
+// Wire coordinates count from the top left of the screen the canvas started on, canvas texels from its bottom left
+fn to_wire(p: Vec2, screen_height: f32) -> Vec2 {
+    vec2(p.x, screen_height - p.y)
+}
+
+fn to_canvas(p: Vec2, screen_height: f32) -> Vec2 {
+    vec2(p.x, screen_height - p.y)
+}
+
+fn map_points(event: StrokeEvent, f: impl Fn(Vec2) -> Vec2) -> StrokeEvent {
+    match event {
+        StrokeEvent::Paint { color, from, to, size } => StrokeEvent::Paint { color, from: f(from), to: f(to), size },
+        event => event
+    }
+}
+
+// What applying the event to a history does to its canvas, on tiles without a history
+fn draw_event(event: &StrokeEvent, surface: &dyn Surface, compute_shader: &ComputeShader) {
+    match *event {
+        StrokeEvent::Paint { color, from, to, size } => paint(compute_shader, surface, color, to, from, size),
+        StrokeEvent::Clear => surface.clear(),
+        StrokeEvent::Begin | StrokeEvent::Revert => {}
+    }
+}
+
+// Width and height from a PNG's header
+fn png_size(png: &[u8]) -> Option<(u32, u32)> {
+    let header = png.get(16..24)?;
+    Some((u32::from_be_bytes(header[..4].try_into().ok()?), u32::from_be_bytes(header[4..].try_into().ok()?)))
+}
+
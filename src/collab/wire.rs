@@ -0,0 +1,636 @@
+use std::collections::BTreeMap;
+use std::io::{ErrorKind, Read, Write};
+use std::net::{TcpListener, TcpStream, ToSocketAddrs};
+use std::time::{Duration, Instant};
+use serde::{Deserialize, Serialize};
+use crate::brush::MAX_BRUSH_SIZE;
+
+// Peers that speak another version are refused during the handshake
+pub const PROTOCOL_VERSION: u32 = 2;
+pub const DEFAULT_PORT: u16 = 7171;
+// The host is always peer 0, guests are numbered from 1 in the order they join
+pub const HOST: u32 = 0;
+// A peer sending longer lines, or reading so slowly that this much piles up for it, is dropped.
+// The longest lines are a tile of pixels
+const MAX_LINE: usize = 1 << 20;
+const MAX_BACKLOG: usize = 64 << 20;
+// Guests that don't say hello in time are dropped, and only this many may be waiting to at once
+const HANDSHAKE_TIMEOUT: Duration = Duration::from_secs(5);
+const MAX_PENDING: usize = 8;
+// Names go next to cursors, longer ones are cut
+const MAX_NAME: usize = 32;
+// Points further out than this aren't on anybody's screen
+const MAX_COORDINATE: f32 = 1e5;
+
+// One JSON object per line in both directions. A guest starts with hello and gets welcome or refused back,
+// after that both sides send whatever happens on their canvas. The host passes everything it gets on to
+// the other guests, so every peer sees every stroke. Points are [x, y] in pixels from the top left of the
+// screen each canvas started on, wherever the view is panned or zoomed to. Pages count from 0
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum Message {
+    // The secret the host was started with, empty if it has none
+    Hello { protocol: u32, name: String, #[serde(default)] secret: String },
+    Welcome { protocol: u32, id: u32 },
+    Refused { reason: String },
+    Joined { peer: u32, name: String },
+    Left { peer: u32 },
+    // No position while the peer's overlay is hidden or presenting
+    Cursor { peer: u32, position: Option<[f32; 2]>, color: [f32; 4] },
+    // A stroke from one of the peer's tools, it becomes an undo step of its own once it ends. The tool is only informative
+    Begin { peer: u32, page: usize, tool: String },
+    Paint { peer: u32, page: usize, color: [f32; 4], from: [f32; 2], to: [f32; 2], size: i32 },
+    // Drops what the stroke painted so far
+    Revert { peer: u32, page: usize },
+    Clear { peer: u32, page: usize },
+    End { peer: u32, page: usize },
+    // Pixels that changed any other way, like an undo or a fill, as a base64 PNG with its top left corner at the position
+    Pixels { peer: u32, page: usize, position: [i32; 2], png: String }
+}
+
+impl Message {
+    fn peer(&self) -> Option<u32> {
+        match *self {
+            Message::Joined { peer, .. } | Message::Left { peer } | Message::Cursor { peer, .. } | Message::Begin { peer, .. } |
+            Message::Paint { peer, .. } | Message::Revert { peer, .. } | Message::Clear { peer, .. } |
+            Message::End { peer, .. } | Message::Pixels { peer, .. } => Some(peer),
+            Message::Hello { .. } | Message::Welcome { .. } | Message::Refused { .. } => None
+        }
+    }
+
+    // Guests can't speak for anyone else, the host stamps their messages with who sent them
+    fn set_peer(&mut self, id: u32) {
+        match self {
+            Message::Joined { peer, .. } | Message::Left { peer } | Message::Cursor { peer, .. } | Message::Begin { peer, .. } |
+            Message::Paint { peer, .. } | Message::Revert { peer, .. } | Message::Clear { peer, .. } |
+            Message::End { peer, .. } | Message::Pixels { peer, .. } => *peer = id,
+            Message::Hello { .. } | Message::Welcome { .. } | Message::Refused { .. } => {}
+        }
+    }
+
+    // What a guest may send once it joined, who's there is only for the host to say
+    fn is_from_guest(&self) -> bool {
+        matches!(self, Message::Cursor { .. } | Message::Begin { .. } | Message::Paint { .. } | Message::Revert { .. } |
+            Message::Clear { .. } | Message::End { .. } | Message::Pixels { .. })
+    }
+
+    // Holds what came in to what this end is willing to draw, anything that isn't a number is refused
+    fn check(&mut self) -> Result<(), String> {
+        let on_screen = |point: &[f32; 2]| point.iter().all(|c| c.is_finite() && c.abs() <= MAX_COORDINATE);
+        let is_color = |color: &[f32; 4]| color.iter().all(|c| c.is_finite());
+        match self {
+            Message::Hello { name, .. } | Message::Joined { name, .. } => *name = name.chars().take(MAX_NAME).collect(),
+            Message::Cursor { position, color, .. } => {
+                if !position.is_none_or(|position| on_screen(&position)) || !is_color(color) {
+                    return Err("cursor with a position or color that isn't a number".to_string());
+                }
+                *color = color.map(|c| c.clamp(0.0, 1.0));
+            }
+            Message::Paint { color, from, to, size, .. } => {
+                if !on_screen(from) || !on_screen(to) || !is_color(color) {
+                    return Err("paint with a point or color that isn't a number".to_string());
+                }
+                *color = color.map(|c| c.clamp(0.0, 1.0));
+                *size = (*size).clamp(1, MAX_BRUSH_SIZE);
+            }
+            Message::Pixels { position, .. } if position.iter().any(|c| c.unsigned_abs() as f32 > MAX_COORDINATE) => {
+                return Err("pixels that aren't on the screen".to_string());
+            }
+            _ => {}
+        }
+        Ok(())
+    }
+}
+
+// Where the port is left out, the default one is used
+fn with_default_port(address: &str) -> String {
+    if address.parse::<std::net::SocketAddr>().is_ok() || address.rsplit_once(':').is_some_and(|(_, port)| port.parse::<u16>().is_ok()) {
+        address.to_string()
+    } else {
+        format!("{}:{}", address, DEFAULT_PORT)
+    }
+}
+
+// A bare port, with or without the colon, only listens on this machine
+fn host_address(address: &str) -> String {
+    let port = address.strip_prefix(':').unwrap_or(address);
+    if port.parse::<u16>().is_ok() {
+        format!("127.0.0.1:{}", port)
+    } else {
+        with_default_port(address)
+    }
+}
+
+// Writes are queued and go out as the socket takes them, a slow peer never stalls the frame
+struct Connection {
+    stream: TcpStream,
+    incoming: Vec<u8>,
+    outgoing: Vec<u8>,
+    // Close once everything queued went out, for refusals
+    closing: bool,
+    closed: bool
+}
+
+impl Connection {
+    fn new(stream: TcpStream) -> std::io::Result<Self> {
+        stream.set_nonblocking(true)?;
+        // Strokes are lots of small messages that should show up right away
+        stream.set_nodelay(true)?;
+        Ok(Connection { stream, incoming: Vec::new(), outgoing: Vec::new(), closing: false, closed: false })
+    }
+
+    fn send(&mut self, message: &Message) {
+        if let Ok(line) = serde_json::to_string(message) {
+            self.outgoing.extend_from_slice(line.as_bytes());
+            self.outgoing.push(b'\n');
+        }
+    }
+
+    fn flush(&mut self) {
+        while !self.outgoing.is_empty() && !self.closed {
+            match self.stream.write(&self.outgoing) {
+                Ok(0) => self.closed = true,
+                Ok(written) => {
+                    self.outgoing.drain(..written);
+                }
+                Err(error) if error.kind() == ErrorKind::Interrupted => {}
+                Err(error) => {
+                    self.closed |= error.kind() != ErrorKind::WouldBlock;
+                    break;
+                }
+            }
+        }
+        self.closed |= self.outgoing.len() > MAX_BACKLOG || (self.closing && self.outgoing.is_empty());
+    }
+
+    // The complete lines received since the last call
+    fn receive(&mut self) -> Vec<Result<Message, String>> {
+        let mut chunk = [0; 4096];
+        let mut messages = Vec::new();
+        // Lines are taken out as they complete, so only a line that's too long can grow the buffer past the limit
+        loop {
+            while let Some(line) = self.next_line() {
+                messages.push(line);
+            }
+            if self.incoming.len() > MAX_LINE {
+                self.incoming.clear();
+                self.closed = true;
+                messages.push(Err(format!("message is longer than {} bytes", MAX_LINE)));
+            }
+            if self.closed {
+                break;
+            }
+            match self.stream.read(&mut chunk) {
+                Ok(0) => self.closed = true,
+                Ok(read) => self.incoming.extend_from_slice(&chunk[..read]),
+                Err(error) if error.kind() == ErrorKind::Interrupted => {}
+                Err(error) => {
+                    self.closed |= error.kind() != ErrorKind::WouldBlock;
+                    break;
+                }
+            }
+        }
+        messages
+    }
+
+    fn next_line(&mut self) -> Option<Result<Message, String>> {
+        loop {
+            let end = self.incoming.iter().position(|byte| *byte == b'\n')?;
+            let line: Vec<u8> = self.incoming.drain(..=end).collect();
+            let line = String::from_utf8_lossy(&line);
+            if !line.trim().is_empty() {
+                return Some(serde_json::from_str(line.trim()).map_err(|error| format!("invalid message: {}", error)));
+            }
+        }
+    }
+}
+
+struct Guest {
+    id: u32,
+    // None until its hello arrived
+    name: Option<String>,
+    connected: Instant,
+    connection: Connection
+}
+
+enum Role {
+    Host { listener: TcpListener, guests: Vec<Guest>, next_id: u32 },
+    Guest { host: Connection }
+}
+
+// The connections of a session, without anything that draws. The host checks who joins and passes what
+// every guest sends on to the others, a guest only talks to the host
+pub struct Link {
+    role: Role,
+    id: u32,
+    name: String,
+    secret: String,
+    // Who else is there and their last cursor, so newcomers can be told
+    peers: BTreeMap<u32, (String, Option<Message>)>,
+    cursor: Option<Message>
+}
+
+impl Link {
+    pub fn host(address: &str, name: &str, secret: &str) -> Result<Self, String> {
+        let address = host_address(address);
+        let listener = TcpListener::bind(&address).map_err(|error| format!("can't listen on {}: {}", address, error))?;
+        listener.set_nonblocking(true).map_err(|error| error.to_string())?;
+        if secret.is_empty() && listener.local_addr().is_ok_and(|local| !local.ip().is_loopback()) {
+            eprintln!("[WARNING::COLLAB::HOST] listening on {} without a secret, anyone who can reach it can draw on this screen", address);
+        }
+        let role = Role::Host { listener, guests: Vec::new(), next_id: HOST + 1 };
+        Ok(Link { role, id: HOST, name: name.to_string(), secret: secret.to_string(), peers: BTreeMap::new(), cursor: None })
+    }
+
+    // Waits for the host's answer, so a refusal is reported before the overlay shows up
+    pub fn join(address: &str, name: &str, secret: &str) -> Result<Self, String> {
+        let address = with_default_port(address);
+        let fail = |error: String| format!("can't join {}: {}", address, error);
+        let addresses: Vec<_> = address.to_socket_addrs().map_err(|error| fail(error.to_string()))?.collect();
+        let mut stream = Err(fail("no address to connect to".to_string()));
+        for candidate in addresses {
+            stream = TcpStream::connect_timeout(&candidate, HANDSHAKE_TIMEOUT).map_err(|error| fail(error.to_string()));
+            if stream.is_ok() {
+                break;
+            }
+        }
+        let stream = stream?;
+
+        let mut host = Connection { stream, incoming: Vec::new(), outgoing: Vec::new(), closing: false, closed: false };
+        host.send(&Message::Hello { protocol: PROTOCOL_VERSION, name: name.to_string(), secret: secret.to_string() });
+        host.stream.set_read_timeout(Some(HANDSHAKE_TIMEOUT)).map_err(|error| fail(error.to_string()))?;
+        host.stream.write_all(&host.outgoing).map_err(|error| fail(error.to_string()))?;
+        host.outgoing.clear();
+        let answer = loop {
+            if let Some(line) = host.next_line() {
+                break line.map_err(fail)?;
+            }
+            let mut chunk = [0; 4096];
+            match host.stream.read(&mut chunk) {
+                Ok(0) => return Err(fail("the host hung up".to_string())),
+                Ok(read) => host.incoming.extend_from_slice(&chunk[..read]),
+                Err(error) if error.kind() == ErrorKind::Interrupted => {}
+                Err(error) if matches!(error.kind(), ErrorKind::WouldBlock | ErrorKind::TimedOut) => return Err(fail("the host didn't answer".to_string())),
+                Err(error) => return Err(fail(error.to_string()))
+            }
+            if host.incoming.len() > MAX_LINE {
+                return Err(fail("the host doesn't speak the overdraw protocol".to_string()));
+            }
+        };
+        let id = match answer {
+            Message::Welcome { protocol, id } if protocol == PROTOCOL_VERSION => id,
+            Message::Welcome { protocol, .. } => return Err(fail(format!("the host speaks protocol {}, this is {}", protocol, PROTOCOL_VERSION))),
+            Message::Refused { reason } => return Err(fail(format!("refused: {}", reason))),
+            _ => return Err(fail("the host didn't welcome us".to_string()))
+        };
+
+        // Whatever came in behind the welcome stays in the buffer for the first poll
+        host.stream.set_read_timeout(None).map_err(|error| fail(error.to_string()))?;
+        host.stream.set_nonblocking(true).map_err(|error| fail(error.to_string()))?;
+        host.stream.set_nodelay(true).map_err(|error| fail(error.to_string()))?;
+        Ok(Link { role: Role::Guest { host }, id, name: name.to_string(), secret: secret.to_string(), peers: BTreeMap::new(), cursor: None })
+    }
+
+    pub fn id(&self) -> u32 {
+        self.id
+    }
+
+    // What the others sent since the last call, stamped with who sent it, along with who joined and left.
+    // Fails once a guest lost its host
+    pub fn poll(&mut self) -> Result<Vec<Message>, String> {
+        let mut received = Vec::new();
+        match &mut self.role {
+            Role::Host { listener, guests, next_id } => {
+                // Connections past the limit are closed right away by dropping them
+                while let Ok((stream, _)) = listener.accept() {
+                    if guests.iter().filter(|guest| guest.name.is_none()).count() >= MAX_PENDING {
+                        continue;
+                    }
+                    if let Ok(connection) = Connection::new(stream) {
+                        guests.push(Guest { id: *next_id, name: None, connected: Instant::now(), connection });
+                        *next_id += 1;
+                    }
+                }
+                for guest in guests.iter_mut() {
+                    for message in guest.connection.receive() {
+                        received.push((guest.id, message));
+                    }
+                }
+                for guest in guests.iter_mut().filter(|guest| guest.name.is_none() && guest.connected.elapsed() > HANDSHAKE_TIMEOUT) {
+                    guest.connection.closed = true;
+                }
+            }
+            Role::Guest { host } => {
+                for message in host.receive() {
+                    received.push((HOST, message));
+                }
+            }
+        }
+
+        let mut messages = Vec::new();
+        for (from, message) in received {
+            let checked = message.and_then(|mut message| {
+                message.check()?;
+                Ok(message)
+            });
+            match checked {
+                Ok(message) => messages.extend(self.receive(from, message)),
+                Err(error) => eprintln!("[ERROR::COLLAB::RECEIVE] peer {}: {}", from, error)
+            }
+        }
+
+        match &mut self.role {
+            Role::Host { guests, .. } => {
+                for guest in guests.iter_mut() {
+                    guest.connection.flush();
+                }
+                let left: Vec<(u32, bool)> = guests.iter().filter(|guest| guest.connection.closed).map(|guest| (guest.id, guest.name.is_some())).collect();
+                guests.retain(|guest| !guest.connection.closed);
+                for (id, joined) in left {
+                    if joined {
+                        let left = Message::Left { peer: id };
+                        self.peers.remove(&id);
+                        self.broadcast(&left, None);
+                        messages.push(left);
+                    }
+                }
+                // The others hear who left right away instead of with whatever goes out next
+                if let Role::Host { guests, .. } = &mut self.role {
+                    for guest in guests.iter_mut() {
+                        guest.connection.flush();
+                    }
+                }
+                Ok(messages)
+            }
+            Role::Guest { host } => {
+                host.flush();
+                if host.closed {
+                    return Err("the host ended the session".to_string());
+                }
+                Ok(messages)
+            }
+        }
+    }
+
+    fn receive(&mut self, from: u32, mut message: Message) -> Option<Message> {
+        if let Role::Host { guests, .. } = &mut self.role {
+            let guest = guests.iter_mut().find(|guest| guest.id == from)?;
+            if guest.name.is_none() {
+                let refusal = match &message {
+                    Message::Hello { protocol, .. } if *protocol != PROTOCOL_VERSION => Some(format!("this host speaks protocol {}, not {}", PROTOCOL_VERSION, protocol)),
+                    Message::Hello { secret, .. } if *secret != self.secret => Some("wrong secret".to_string()),
+                    Message::Hello { .. } => None,
+                    _ => Some("expected hello first".to_string())
+                };
+                let name = match (message, refusal) {
+                    (Message::Hello { name, .. }, None) => name,
+                    (_, reason) => {
+                        guest.connection.send(&Message::Refused { reason: reason.unwrap_or_default() });
+                        guest.connection.closing = true;
+                        return None;
+                    }
+                };
+                // The newcomer learns who's there and where their cursors are, the others learn about the newcomer
+                guest.connection.send(&Message::Welcome { protocol: PROTOCOL_VERSION, id: from });
+                guest.connection.send(&Message::Joined { peer: HOST, name: self.name.clone() });
+                if let Some(cursor) = &self.cursor {
+                    guest.connection.send(cursor);
+                }
+                for (id, (name, cursor)) in &self.peers {
+                    guest.connection.send(&Message::Joined { peer: *id, name: name.clone() });
+                    if let Some(cursor) = cursor {
+                        guest.connection.send(cursor);
+                    }
+                }
+                guest.name = Some(name.clone());
+                let joined = Message::Joined { peer: from, name };
+                self.remember(&joined);
+                self.broadcast(&joined, Some(from));
+                return Some(joined);
+            }
+            if !message.is_from_guest() {
+                return None;
+            }
+            message.set_peer(from);
+            self.broadcast(&message, Some(from));
+        } else if message.peer().is_none_or(|peer| peer == self.id) {
+            return None;
+        }
+        self.remember(&message);
+        Some(message)
+    }
+
+    fn remember(&mut self, message: &Message) {
+        match message {
+            Message::Joined { peer, name } => {
+                self.peers.insert(*peer, (name.clone(), None));
+            }
+            Message::Left { peer } => {
+                self.peers.remove(peer);
+            }
+            Message::Cursor { peer, .. } => {
+                if let Some((_, cursor)) = self.peers.get_mut(peer) {
+                    *cursor = Some(message.clone());
+                }
+            }
+            _ => {}
+        }
+    }
+
+    // To everyone else, stamped with this end's id
+    pub fn send(&mut self, mut message: Message) {
+        message.set_peer(self.id);
+        if let Message::Cursor { .. } = message {
+            self.cursor = Some(message.clone());
+        }
+        self.broadcast(&message, None);
+    }
+
+    // To every guest but one for the host, to the host for a guest
+    fn broadcast(&mut self, message: &Message, except: Option<u32>) {
+        match &mut self.role {
+            Role::Host { guests, .. } => {
+                for guest in guests.iter_mut().filter(|guest| guest.name.is_some() && Some(guest.id) != except) {
+                    guest.connection.send(message);
+                }
+            }
+            Role::Guest { host } => host.send(message)
+        }
+    }
+
+    #[cfg(test)]
+    fn local_addr(&self) -> std::net::SocketAddr {
+        match &self.role {
+            Role::Host { listener, .. } => listener.local_addr().unwrap(),
+            Role::Guest { host } => host.stream.peer_addr().unwrap()
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::{BufRead, BufReader};
+    use std::thread;
+
+    fn host() -> Link {
+        Link::host("127.0.0.1:0", "host", "chalk").unwrap()
+    }
+
+    // The host only answers while it's polled, so the guest joins from another thread
+    fn join(host: &mut Link, name: &str, secret: &str) -> (Result<Link, String>, Vec<Message>) {
+        let (address, name, secret) = (host.local_addr().to_string(), name.to_string(), secret.to_string());
+        let joining = thread::spawn(move || Link::join(&address, &name, &secret));
+        let mut seen = Vec::new();
+        while !joining.is_finished() {
+            seen.extend(host.poll().unwrap());
+            thread::sleep(Duration::from_millis(5));
+        }
+        (joining.join().unwrap(), seen)
+    }
+
+    // Polls until something came in or a second went by
+    fn receive(link: &mut Link) -> Vec<Message> {
+        let start = Instant::now();
+        loop {
+            let messages = link.poll().unwrap();
+            if !messages.is_empty() || start.elapsed() > Duration::from_secs(1) {
+                return messages;
+            }
+            thread::sleep(Duration::from_millis(5));
+        }
+    }
+
+    fn paint(size: i32, from: [f32; 2]) -> Message {
+        Message::Paint { peer: 7, page: 0, color: [1.0, 0.0, 0.0, 1.0], from, to: [10.0, 10.0], size }
+    }
+
+    #[test]
+    fn bare_ports_listen_on_loopback() {
+        assert_eq!(host_address("7171"), "127.0.0.1:7171");
+        assert_eq!(host_address(":8000"), "127.0.0.1:8000");
+        assert_eq!(host_address("0.0.0.0:7171"), "0.0.0.0:7171");
+        assert_eq!(host_address("192.168.1.2"), "192.168.1.2:7171");
+    }
+
+    #[test]
+    fn handshake_welcomes_and_introduces() {
+        let mut host = host();
+        let (ada, seen) = join(&mut host, "ada", "chalk");
+        let mut ada = ada.unwrap();
+        assert_eq!(ada.id(), 1);
+        assert_eq!(seen, vec![Message::Joined { peer: 1, name: "ada".to_string() }]);
+        assert_eq!(receive(&mut ada), vec![Message::Joined { peer: HOST, name: "host".to_string() }]);
+
+        let (bob, _) = join(&mut host, "bob", "chalk");
+        let mut bob = bob.unwrap();
+        assert_eq!(bob.id(), 2);
+        assert_eq!(receive(&mut ada), vec![Message::Joined { peer: 2, name: "bob".to_string() }]);
+        assert_eq!(receive(&mut bob), vec![
+            Message::Joined { peer: HOST, name: "host".to_string() },
+            Message::Joined { peer: 1, name: "ada".to_string() }
+        ]);
+    }
+
+    #[test]
+    fn refuses_other_versions_and_wrong_secrets() {
+        let mut host = host();
+        let (joined, seen) = join(&mut host, "mallory", "cheese");
+        assert_eq!(joined.err().unwrap(), format!("can't join {}: refused: wrong secret", host.local_addr()));
+        assert!(seen.is_empty());
+
+        let address = host.local_addr();
+        let talking = thread::spawn(move || {
+            let mut stream = TcpStream::connect(address).unwrap();
+            stream.write_all(b"{\"type\":\"hello\",\"protocol\":1,\"name\":\"old\",\"secret\":\"chalk\"}\n").unwrap();
+            let mut answer = String::new();
+            BufReader::new(stream).read_line(&mut answer).unwrap();
+            answer
+        });
+        while !talking.is_finished() {
+            assert!(host.poll().unwrap().is_empty());
+            thread::sleep(Duration::from_millis(5));
+        }
+        let answer: Message = serde_json::from_str(&talking.join().unwrap()).unwrap();
+        assert_eq!(answer, Message::Refused { reason: format!("this host speaks protocol {}, not 1", PROTOCOL_VERSION) });
+    }
+
+    #[test]
+    fn relays_strokes_stamped_with_the_sender() {
+        let mut host = host();
+        let mut ada = join(&mut host, "ada", "chalk").0.unwrap();
+        let mut bob = join(&mut host, "bob", "chalk").0.unwrap();
+        receive(&mut ada);
+        receive(&mut ada);
+        receive(&mut bob);
+
+        ada.send(paint(4, [0.0, 0.0]));
+        ada.poll().unwrap();
+        let relayed = Message::Paint { peer: 1, page: 0, color: [1.0, 0.0, 0.0, 1.0], from: [0.0, 0.0], to: [10.0, 10.0], size: 4 };
+        assert_eq!(receive(&mut host), vec![relayed.clone()]);
+        assert_eq!(receive(&mut bob), vec![relayed]);
+        assert!(receive(&mut ada).is_empty());
+
+        host.send(Message::End { peer: 5, page: 0 });
+        host.poll().unwrap();
+        assert_eq!(receive(&mut ada), vec![Message::End { peer: HOST, page: 0 }]);
+        assert_eq!(receive(&mut bob), vec![Message::End { peer: HOST, page: 0 }]);
+    }
+
+    #[test]
+    fn holds_strokes_to_what_can_be_drawn() {
+        let mut host = host();
+        let mut ada = join(&mut host, "ada", "chalk").0.unwrap();
+        receive(&mut ada);
+
+        // A point off every screen is dropped, an oversized brush is clamped
+        ada.send(paint(4, [0.0, 1e30]));
+        ada.send(paint(100_000, [0.0, 0.0]));
+        ada.poll().unwrap();
+        let clamped = Message::Paint { peer: 1, page: 0, color: [1.0, 0.0, 0.0, 1.0], from: [0.0, 0.0], to: [10.0, 10.0], size: MAX_BRUSH_SIZE };
+        assert_eq!(receive(&mut host), vec![clamped]);
+
+        // JSON has no NaN, but numbers too big for an f32 come out as infinity
+        let mut nan = paint(4, [0.0, 0.0]);
+        if let Message::Paint { color, .. } = &mut nan {
+            color[0] = f32::NAN;
+        }
+        assert!(nan.check().is_err());
+        let mut infinite: Message = serde_json::from_str(r#"{"type":"paint","peer":1,"page":0,"color":[1e300,0,0,1],"from":[0,0],"to":[0,0],"size":4}"#).unwrap();
+        assert!(infinite.check().is_err());
+    }
+
+    #[test]
+    fn tells_everyone_who_left() {
+        let mut host = host();
+        let ada = join(&mut host, "ada", "chalk").0.unwrap();
+        let mut bob = join(&mut host, "bob", "chalk").0.unwrap();
+        receive(&mut bob);
+
+        drop(ada);
+        assert_eq!(receive(&mut host), vec![Message::Left { peer: 1 }]);
+        assert_eq!(receive(&mut bob), vec![Message::Left { peer: 1 }]);
+
+        drop(host);
+        let start = Instant::now();
+        while bob.poll().is_ok() {
+            assert!(start.elapsed() < Duration::from_secs(1));
+            thread::sleep(Duration::from_millis(5));
+        }
+    }
+
+    #[test]
+    fn drops_connections_past_the_pending_limit() {
+        let mut host = host();
+        let waiting: Vec<TcpStream> = (0..MAX_PENDING).map(|_| TcpStream::connect(host.local_addr()).unwrap()).collect();
+        let mut extra = TcpStream::connect(host.local_addr()).unwrap();
+        extra.set_read_timeout(Some(Duration::from_secs(1))).unwrap();
+        let start = Instant::now();
+        while start.elapsed() < Duration::from_millis(100) {
+            host.poll().unwrap();
+            thread::sleep(Duration::from_millis(5));
+        }
+        assert_eq!(extra.read(&mut [0; 16]).unwrap(), 0);
+        drop(waiting);
+    }
+}
@@ -0,0 +1,39 @@
+use glm::{vec4, Vec2, Vec4};
+use crate::tiles::Surface;
+
+pub struct Eyedropper {
+    pub radius: i32
+}
+
+impl Eyedropper {
+    pub fn new() -> Self {
+        Eyedropper { radius: 0 }
+    }
+
+    // Average color of the ink within the radius around the cursor, weighted by alpha so transparent pixels don't darken it
+    pub fn sample(&self, surface: &dyn Surface, cursor: Vec2) -> Option<Vec4> {
+        let radius = self.radius.max(0);
+        let (cx, cy) = (cursor.x.floor() as i32, cursor.y.floor() as i32);
+        let (x0, y0, x1, y1) = (cx - radius, cy - radius, cx + radius + 1, cy + radius + 1);
+        let (width, height) = ((x1 - x0) as u32, (y1 - y0) as u32);
+        let pixels = surface.read(x0, y0, width, height);
+
+        let mut sum = vec4(0.0, 0.0, 0.0, 0.0);
+        let mut count = 0;
+        for y in y0..y1 {
+            for x in x0..x1 {
+                if (x - cx) * (x - cx) + (y - cy) * (y - cy) > radius * radius {
+                    continue;
+                }
+                let i = (((y - y0) as u32 * width + (x - x0) as u32) * 4) as usize;
+                let alpha = pixels[i + 3];
+                sum = sum + vec4(pixels[i] * alpha, pixels[i + 1] * alpha, pixels[i + 2] * alpha, alpha);
+                count += 1;
+            }
+        }
+        if sum.w <= 0.0 {
+            return None;
+        }
+        Some(vec4(sum.x / sum.w, sum.y / sum.w, sum.z / sum.w, sum.w / count as f32))
+    }
+}
@@ -0,0 +1,129 @@
+use std::thread;
+use glm::{Vec2, Vec4};
+use crate::brush::over;
+use crate::history::History;
+use crate::tiles::Surface;
+
+pub struct Bucket {
+    pub tolerance: f32,
+    pub through_edges: bool
+}
+
+impl Bucket {
+    pub fn new() -> Self {
+        Bucket { tolerance: 0.1, through_edges: true }
+    }
+
+    // Fills the region under the cursor on a new history entry. The canvas has no edges, so the fill stops at those of the area
+    pub fn fill(&self, history: &mut History, cursor: Vec2, color: Vec4, (min_corner, max_corner): (Vec2, Vec2)) {
+        let (width, height) = ((max_corner.x - min_corner.x) as usize, (max_corner.y - min_corner.y) as usize);
+        let local = cursor - min_corner;
+        if local.x < 0.0 || local.y < 0.0 || local.x >= width as f32 || local.y >= height as f32 {
+            return;
+        }
+
+        let (left, bottom) = (min_corner.x as i32, min_corner.y as i32);
+        let mut pixels = history.get_current().read(left, bottom, width as u32, height as u32);
+        let Some((x0, y0, x1, y1)) = flood_fill(&mut pixels, width, height, (local.x as usize, local.y as usize), color, self) else {
+            return;
+        };
+
+        let region: Vec<f32> = (y0..y1).flat_map(|y| &pixels[(y * width + x0) * 4..(y * width + x1) * 4]).copied().collect();
+        history.begin_step();
+        history.get_current().write(left + x0 as i32, bottom + y0 as i32, (x1 - x0) as u32, (y1 - y0) as u32, &region);
+    }
+}
+
+// Scanline fill on tightly packed RGBA pixels, returns the region it changed as min and max (exclusive) corner
+pub fn flood_fill(pixels: &mut [f32], width: usize, height: usize, (x, y): (usize, usize), color: Vec4, bucket: &Bucket) -> Option<(usize, usize, usize, usize)> {
+    let target = premultiplied(&pixels[(y * width + x) * 4..][..4]);
+    let fill = [color.x, color.y, color.z, color.w];
+    if distance(target, premultiplied(&fill)) <= 0.0 {
+        return None;
+    }
+
+    // Comparing every pixel against the target is the expensive part on big canvases, so it's split across threads by rows
+    let mut matches = vec![false; width * height];
+    let threads = thread::available_parallelism().map_or(1, |n| n.get());
+    let rows_per_thread = height.div_ceil(threads);
+    thread::scope(|scope| {
+        for (rows, matches) in pixels.chunks(rows_per_thread * width * 4).zip(matches.chunks_mut(rows_per_thread * width)) {
+            scope.spawn(move || {
+                for (pixel, matched) in rows.chunks_exact(4).zip(matches.iter_mut()) {
+                    *matched = distance(premultiplied(pixel), target) <= bucket.tolerance;
+                }
+            });
+        }
+    });
+
+    // The rows and columns the fill reached, so the passes below only look at that part of the area
+    let mut filled = vec![false; width * height];
+    let (mut x0, mut y0, mut x1, mut y1) = (width, height, 0, 0);
+    let mut seeds = vec![(x, y)];
+    while let Some((x, y)) = seeds.pop() {
+        if filled[y * width + x] || !matches[y * width + x] {
+            continue;
+        }
+
+        let mut left = x;
+        while left > 0 && matches[y * width + left - 1] && !filled[y * width + left - 1] {
+            left -= 1;
+        }
+        let mut right = x;
+        while right + 1 < width && matches[y * width + right + 1] && !filled[y * width + right + 1] {
+            right += 1;
+        }
+
+        for row in [y.wrapping_sub(1), y + 1] {
+            if row >= height {
+                continue;
+            }
+            let mut in_span = false;
+            for column in left..=right {
+                let open = matches[row * width + column] && !filled[row * width + column];
+                if open && !in_span {
+                    seeds.push((column, row));
+                }
+                in_span = open;
+            }
+        }
+        filled[y * width + left..=y * width + right].fill(true);
+        x0 = x0.min(left);
+        y0 = y0.min(y);
+        x1 = x1.max(right + 1);
+        y1 = y1.max(y + 1);
+    }
+
+    // Anti-aliased edges don't match the target, so the fill is grown under them by a pixel to avoid a halo
+    let mut edges = vec![false; width * height];
+    if bucket.through_edges {
+        (x0, y0, x1, y1) = (x0.saturating_sub(1), y0.saturating_sub(1), (x1 + 1).min(width), (y1 + 1).min(height));
+        for y in y0..y1 {
+            for x in x0..x1 {
+                let i = y * width + x;
+                edges[i] = !filled[i] && ((x > 0 && filled[i - 1]) || (x + 1 < width && filled[i + 1]) || (y > 0 && filled[i - width]) || (y + 1 < height && filled[i + width]));
+            }
+        }
+    }
+
+    for y in y0..y1 {
+        for x in x0..x1 {
+            let i = y * width + x;
+            if filled[i] {
+                pixels[i * 4..i * 4 + 4].copy_from_slice(&fill);
+            } else if edges[i] {
+                let blended = over(&pixels[i * 4..i * 4 + 4], &fill);
+                pixels[i * 4..i * 4 + 4].copy_from_slice(&blended);
+            }
+        }
+    }
+    Some((x0, y0, x1, y1))
+}
+
+fn premultiplied(pixel: &[f32]) -> [f32; 4] {
+    [pixel[0] * pixel[3], pixel[1] * pixel[3], pixel[2] * pixel[3], pixel[3]]
+}
+
+fn distance(a: [f32; 4], b: [f32; 4]) -> f32 {
+    (0..4).map(|i| (a[i] - b[i]).abs()).fold(0.0, f32::max)
+}
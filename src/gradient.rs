@@ -0,0 +1,91 @@
+use glm::*;
+use serde::{Deserialize, Serialize};
+use crate::history::History;
+use crate::input::Input;
+use crate::open_gl::shaders::ComputeShader;
+use crate::tiles::Surface;
+
+// Keep in sync with the stop arrays in GRADIENT_SHADER
+pub const MAX_STOPS: usize = 8;
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum GradientMode {
+    Linear,
+    Radial
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct GradientStop {
+    pub position: f32,
+    pub color: Vec4
+}
+
+pub struct Gradient {
+    pub mode: GradientMode,
+    pub stops: Vec<GradientStop>,
+    pub ink_only: bool,
+    start: Option<Vec2>,
+    end: Vec2
+}
+
+impl Gradient {
+    pub fn new() -> Self {
+        Gradient {
+            mode: GradientMode::Linear,
+            stops: vec![
+                GradientStop { position: 0.0, color: vec4(0.2, 0.5, 1.0, 0.8) },
+                GradientStop { position: 1.0, color: vec4(0.6, 0.2, 1.0, 0.8) }
+            ],
+            ink_only: false,
+            start: None,
+            end: vec2(0.0, 0.0)
+        }
+    }
+
+    // The canvas has no edges, a gradient under the ink covers the area
+    pub fn update(&mut self, input: &Input, cursor: Vec2, history: &mut History, gradient_shader: &ComputeShader, area: (Vec2, Vec2)) {
+        if input.get_button_down(1) {
+            self.start = Some(cursor);
+        }
+        self.end = cursor;
+        if !input.get_button(1) {
+            if let Some(start) = self.start.take() {
+                self.apply(history, start, cursor, gradient_shader, area);
+            }
+        }
+    }
+
+    // Direction that is currently being dragged out
+    pub fn outline(&self) -> Option<Vec<Vec2>> {
+        Some(vec![self.start?, self.end])
+    }
+
+    fn apply(&self, history: &mut History, start: Vec2, end: Vec2, gradient_shader: &ComputeShader, (min_corner, max_corner): (Vec2, Vec2)) {
+        if start == end || self.stops.is_empty() {
+            return;
+        }
+
+        let mut stops = self.stops.clone();
+        stops.truncate(MAX_STOPS);
+        stops.sort_by(|a, b| a.position.total_cmp(&b.position));
+        let positions: Vec<f32> = stops.iter().map(|stop| stop.position).collect();
+        let colors: Vec<Vec4> = stops.iter().map(|stop| stop.color).collect();
+
+        history.begin_step();
+        gradient_shader.set_vector2(&start, "start");
+        gradient_shader.set_vector2(&end, "end");
+        gradient_shader.set_int((self.mode == GradientMode::Radial) as i32, "radial");
+        gradient_shader.set_int(self.ink_only as i32, "ink_only");
+        gradient_shader.set_int(stops.len() as i32, "stop_count");
+        gradient_shader.set_float_array(&positions, "stop_positions");
+        gradient_shader.set_vector4_array(&colors, "stop_colors");
+
+        // Recoloring only touches the ink there is, wherever it is
+        let tiles = history.get_current();
+        let area = if self.ink_only { tiles.bounds() } else { Some((min_corner, max_corner)) };
+        if let Some((min_corner, max_corner)) = area {
+            tiles.regions(min_corner, max_corner, !self.ink_only, &mut |region| region.dispatch(gradient_shader));
+        }
+    }
+}
@@ -1,8 +1,17 @@
-use glm::Vec4;
 use imgui_glfw_rs::glfw::Window;
-use imgui_glfw_rs::imgui::{Context, EditableColor, im_str, ImGuiWindowFlags, StyleColor, Ui};
+use imgui_glfw_rs::imgui::{Context, EditableColor, im_str, ImGuiWindowFlags, ImString, StyleColor, Ui};
 use imgui_glfw_rs::ImguiGLFW;
-use crate::history::History;
+use crate::background::BackgroundPattern;
+use crate::brush::MAX_BRUSH_SIZE;
+use crate::keybindings::{Action, Keybindings};
+use crate::gradient::{GradientMode, GradientStop, MAX_STOPS};
+use crate::pages::{PageAction, Pages};
+use crate::paths::config_dir;
+use crate::redact::RedactMode;
+use crate::stamp::StampKind;
+use crate::spotlight::SpotlightShape;
+use crate::symmetry::SymmetryMode;
+use crate::tool::{ToolKind, Toolbox};
 
 pub struct Gui{
     pub imgui: Context,
@@ -12,6 +21,14 @@ pub struct Gui{
 impl Gui {
     pub fn new(p_window: &mut Window) -> Self {
         let mut imgui = Context::create();
+        // Window layout is kept with the other settings instead of wherever the program was started from
+        let ini = config_dir().join("imgui.ini");
+        if std::fs::create_dir_all(config_dir()).is_ok() {
+            if !ini.exists() && std::path::Path::new("imgui.ini").exists() {
+                let _ = std::fs::copy("imgui.ini", &ini);
+            }
+            imgui.set_ini_filename(Some(ImString::new(ini.to_string_lossy())));
+        }
         let mut imgui_glfw = ImguiGLFW::new(&mut imgui, p_window);
 
         let style = imgui.style_mut();
@@ -33,7 +50,8 @@ impl Gui {
         self.imgui_glfw.draw(frame, p_window);
     }
 
-    pub fn show_gui(&mut self, p_window: &mut Window, color: &mut Vec4, first_click: &mut bool, mut brush_size: &mut i32, history: &mut History) -> bool {
+    pub fn show_gui(&mut self, p_window: &mut Window, pages: &mut Pages, toolbox: &mut Toolbox, keybindings: &Keybindings, measurement: Option<String>) -> bool {
+        let Toolbox { kind: tool, registry: tools, color, brush_size, recognizer, laser, spotlight, bucket, eyedropper, redactor, gradient, stamps, callout, symmetry, grid, ruler, background, .. } = toolbox;
         let frame = self.imgui_glfw.frame(p_window, &mut self.imgui);
 
         let color_edit = EditableColor::Float4(color.as_array_mut());
@@ -47,13 +65,311 @@ impl Gui {
 
                 if frame.is_window_hovered() || frame.is_window_focused() {
                     is_hovered = true;
-                    *first_click = false;
                 }
 
-                frame.drag_int(im_str!("Brush Size"), &mut brush_size).build();
-                frame.slider_int(im_str!("History Size"), &mut history.max_undos, 1, 25).build();
+                frame.drag_int(im_str!("Brush Size"), brush_size).min(1).max(MAX_BRUSH_SIZE).build();
+                // Typed in values aren't held to the drag range
+                *brush_size = (*brush_size).clamp(1, MAX_BRUSH_SIZE);
+                let mut max_undos = pages.current().max_undos;
+                if frame.slider_int(im_str!("History Size"), &mut max_undos, 1, 25).build() {
+                    pages.set_max_undos(max_undos);
+                }
+            });
+
+        frame.window(im_str!("Tools"))
+            .flags(ImGuiWindowFlags::NoCollapse)
+            .position([0.0, 120.0], imgui_glfw_rs::imgui::Condition::FirstUseEver)
+            .size([300.0, 110.0], imgui_glfw_rs::imgui::Condition::FirstUseEver)
+            .build(|| {
+                if frame.is_window_hovered() || frame.is_window_focused() {
+                    is_hovered = true;
+                }
+
+                // The brush slot holds the brush and every tool that was registered next to it
+                for (index, name) in tools.selectable() {
+                    let label: String = name.chars().take(1).flat_map(char::to_uppercase).chain(name.chars().skip(1)).collect();
+                    if frame.radio_button_bool(&ImString::new(label), *tool == ToolKind::Brush && tools.get_selected() == index) {
+                        *tool = ToolKind::Brush;
+                        tools.select(index);
+                    }
+                }
+                frame.radio_button(im_str!("Rectangle Select"), tool, ToolKind::RectangleSelect);
+                frame.radio_button(im_str!("Lasso Select"), tool, ToolKind::LassoSelect);
+                frame.radio_button(im_str!("Fill"), tool, ToolKind::Fill);
+                frame.radio_button(im_str!("Eyedropper"), tool, ToolKind::Eyedropper);
+                frame.radio_button(im_str!("Redact"), tool, ToolKind::Redact);
+                frame.radio_button(im_str!("Gradient"), tool, ToolKind::Gradient);
+                frame.radio_button(im_str!("Measure"), tool, ToolKind::Measure);
+                frame.radio_button(im_str!("Stamp"), tool, ToolKind::Stamp);
+                frame.radio_button(im_str!("Callout"), tool, ToolKind::Callout);
+                if *tool == ToolKind::Brush {
+                    tools.settings(&frame);
+                    frame.checkbox(im_str!("Laser Pointer"), &mut laser.enabled);
+                    if laser.enabled {
+                        frame.slider_float(im_str!("Fade After (s)"), &mut laser.duration, 0.5, 10.0).build();
+                    }
+                    if let Some(keys) = keybindings.label(Action::StraightLine) {
+                        frame.text(format!("Hold {} for straight lines", keys));
+                    }
+                    frame.checkbox(im_str!("Shape Recognition"), &mut recognizer.enabled);
+                    if recognizer.enabled {
+                        frame.slider_float(im_str!("Confidence"), &mut recognizer.threshold, 0.0, 1.0).build();
+                        frame.slider_float(im_str!("Hold (s)"), &mut recognizer.hold, 0.1, 2.0).build();
+                    }
+                }
+                if tool.is_selection() {
+                    frame.text("Enter: apply, Esc: cancel, Del: delete");
+                }
+                if *tool == ToolKind::Fill {
+                    frame.slider_float(im_str!("Tolerance"), &mut bucket.tolerance, 0.0, 1.0).build();
+                    frame.checkbox(im_str!("Fill Through Edges"), &mut bucket.through_edges);
+                }
+                if *tool == ToolKind::Eyedropper {
+                    frame.slider_int(im_str!("Sample Radius"), &mut eyedropper.radius, 0, 20).build();
+                    if let Some(keys) = keybindings.tool_label(ToolKind::Eyedropper) {
+                        frame.text(format!("Hold {} to pick with any tool", keys));
+                    }
+                }
+                if *tool == ToolKind::Redact {
+                    frame.radio_button(im_str!("Pixelate"), &mut redactor.mode, RedactMode::Pixelate);
+                    frame.same_line(0.0);
+                    frame.radio_button(im_str!("Blur"), &mut redactor.mode, RedactMode::Blur);
+                    frame.same_line(0.0);
+                    frame.radio_button(im_str!("Black Box"), &mut redactor.mode, RedactMode::BlackBox);
+                    if redactor.mode != RedactMode::BlackBox {
+                        frame.slider_int(im_str!("Strength"), &mut redactor.strength, 2, 32).build();
+                    }
+                }
+                if *tool == ToolKind::Measure {
+                    if let Some(keys) = keybindings.label(Action::StraightLine) {
+                        frame.text(format!("Hold {} when releasing to keep the dimension", keys));
+                    }
+                }
+                if *tool == ToolKind::Stamp {
+                    frame.radio_button(im_str!("Numbers"), &mut stamps.kind, StampKind::Number);
+                    for i in 0..stamps.icons.len() {
+                        frame.push_id(i as i32);
+                        frame.radio_button(&ImString::new(stamps.icons[i].name.clone()), &mut stamps.kind, StampKind::Icon(i));
+                        frame.pop_id();
+                    }
+                    if stamps.kind == StampKind::Number {
+                        frame.drag_int(im_str!("Next Number"), &mut stamps.counter).min(0).build();
+                        if frame.small_button(im_str!("Reset")) {
+                            stamps.reset_counter();
+                        }
+                        if let Some(keys) = keybindings.label(Action::ResetCounter) {
+                            frame.same_line(0.0);
+                            frame.text(format!("or press {}", keys));
+                        }
+                    }
+                    if frame.small_button(im_str!("Reload Icons")) {
+                        stamps.reload();
+                    }
+                }
+                if *tool == ToolKind::Callout {
+                    let mut text = ImString::with_capacity(callout.text.len() + 64);
+                    text.push_str(&callout.text);
+                    if frame.input_text_multiline(im_str!("Text"), &mut text, [0.0, 60.0]).resize_buffer(true).build() {
+                        callout.text = text.to_str().to_owned();
+                    }
+                    frame.color_edit(im_str!("Background"), EditableColor::Float4(callout.background.as_array_mut())).build();
+                    frame.color_edit(im_str!("Border"), EditableColor::Float4(callout.border.as_array_mut())).build();
+                    frame.color_edit(im_str!("Text Color"), EditableColor::Float4(callout.text_color.as_array_mut())).build();
+                    frame.slider_int(im_str!("Text Size"), &mut callout.text_scale, 1, 6).build();
+                    if callout.is_placed() {
+                        if frame.small_button(im_str!("Commit")) {
+                            callout.commit(pages.current_mut());
+                        }
+                        frame.same_line(0.0);
+                        if frame.small_button(im_str!("Cancel")) {
+                            callout.cancel();
+                        }
+                    } else {
+                        frame.text("Drag from the target to where the box goes");
+                    }
+                }
+                if *tool == ToolKind::Gradient {
+                    frame.radio_button(im_str!("Linear"), &mut gradient.mode, GradientMode::Linear);
+                    frame.same_line(0.0);
+                    frame.radio_button(im_str!("Radial"), &mut gradient.mode, GradientMode::Radial);
+                    frame.checkbox(im_str!("Only Painted Pixels"), &mut gradient.ink_only);
+
+                    let mut remove = None;
+                    for (i, stop) in gradient.stops.iter_mut().enumerate() {
+                        frame.push_id(i as i32);
+                        frame.color_edit(im_str!("##color"), EditableColor::Float4(stop.color.as_array_mut())).build();
+                        frame.slider_float(im_str!("Position"), &mut stop.position, 0.0, 1.0).build();
+                        if frame.small_button(im_str!("Remove")) {
+                            remove = Some(i);
+                        }
+                        frame.pop_id();
+                    }
+                    if let Some(i) = remove.filter(|_| gradient.stops.len() > 2) {
+                        gradient.stops.remove(i);
+                    }
+                    if gradient.stops.len() < MAX_STOPS && frame.small_button(im_str!("Add Stop")) {
+                        gradient.stops.push(GradientStop { position: 1.0, color: *color });
+                    }
+                }
+            });
+
+        frame.window(im_str!("View"))
+            .flags(ImGuiWindowFlags::NoCollapse)
+            .position([0.0, 240.0], imgui_glfw_rs::imgui::Condition::FirstUseEver)
+            .size([300.0, 110.0], imgui_glfw_rs::imgui::Condition::FirstUseEver)
+            .build(|| {
+                if frame.is_window_hovered() || frame.is_window_focused() {
+                    is_hovered = true;
+                }
+
+                if let Some(keys) = keybindings.label(Action::TogglePresenting) {
+                    frame.text(format!("{}: click-through presenting", keys));
+                }
+                frame.separator();
+                frame.checkbox(im_str!("Spotlight"), &mut spotlight.enabled);
+                if spotlight.enabled {
+                    frame.radio_button(im_str!("Circle"), &mut spotlight.shape, SpotlightShape::Circle);
+                    frame.same_line(0.0);
+                    frame.radio_button(im_str!("Rectangle"), &mut spotlight.shape, SpotlightShape::Rectangle);
+                    frame.drag_int(im_str!("Spotlight Size"), &mut spotlight.size).min(1).build();
+                    if spotlight.shape == SpotlightShape::Rectangle {
+                        frame.slider_float(im_str!("Aspect"), &mut spotlight.aspect, 0.25, 8.0).build();
+                    }
+                    frame.slider_float(im_str!("Dimming"), &mut spotlight.opacity, 0.0, 1.0).build();
+                    if let Some(keys) = keybindings.label(Action::ResizeBrush) {
+                        frame.text(format!("{} + drag resizes the spotlight", keys));
+                    }
+                }
+
+                frame.separator();
+                frame.checkbox(im_str!("Grid"), &mut grid.enabled);
+                if grid.enabled {
+                    frame.drag_int(im_str!("Spacing"), &mut grid.spacing).min(2).build();
+                    frame.color_edit(im_str!("Grid Color"), EditableColor::Float4(grid.color.as_array_mut())).build();
+                    frame.checkbox(im_str!("Snap To Grid"), &mut grid.snap);
+                }
+                frame.checkbox(im_str!("Ruler"), &mut ruler.visible);
+                if ruler.visible {
+                    frame.text("Drag its ends or body, strokes next to its edge follow it");
+                }
+
+                frame.separator();
+                frame.checkbox(im_str!("Background"), &mut background.enabled);
+                if let Some(keys) = keybindings.label(Action::ToggleBackground) {
+                    frame.same_line(0.0);
+                    frame.text(format!("({})", keys));
+                }
+                if background.enabled {
+                    if frame.small_button(im_str!("Whiteboard")) {
+                        background.whiteboard();
+                    }
+                    frame.same_line(0.0);
+                    if frame.small_button(im_str!("Blackboard")) {
+                        background.blackboard();
+                    }
+                    frame.color_edit(im_str!("Board Color"), EditableColor::Float4(background.color.as_array_mut())).build();
+                    frame.radio_button(im_str!("Plain"), &mut background.pattern, BackgroundPattern::Plain);
+                    frame.same_line(0.0);
+                    frame.radio_button(im_str!("Ruled"), &mut background.pattern, BackgroundPattern::Ruled);
+                    frame.same_line(0.0);
+                    frame.radio_button(im_str!("Dotted"), &mut background.pattern, BackgroundPattern::Dotted);
+                    frame.same_line(0.0);
+                    frame.radio_button(im_str!("Graph"), &mut background.pattern, BackgroundPattern::Graph);
+                    if background.pattern != BackgroundPattern::Plain {
+                        frame.drag_int(im_str!("Line Spacing"), &mut background.spacing).min(4).build();
+                        frame.color_edit(im_str!("Line Color"), EditableColor::Float4(background.pattern_color.as_array_mut())).build();
+                    }
+                    if let (Some(hold), Some(pan), Some(zoom)) = (keybindings.label(Action::PanHold), keybindings.label(Action::Pan), keybindings.label(Action::Zoom)) {
+                        frame.text(format!("{} + drag or {} pans, {} + scroll zooms", hold, pan, zoom));
+                    }
+                }
+
+                frame.separator();
+                frame.text("Symmetry");
+                frame.radio_button(im_str!("Off"), &mut symmetry.mode, SymmetryMode::Off);
+                frame.same_line(0.0);
+                frame.radio_button(im_str!("Vertical"), &mut symmetry.mode, SymmetryMode::Vertical);
+                frame.same_line(0.0);
+                frame.radio_button(im_str!("Horizontal"), &mut symmetry.mode, SymmetryMode::Horizontal);
+                frame.same_line(0.0);
+                frame.radio_button(im_str!("Radial"), &mut symmetry.mode, SymmetryMode::Radial);
+                if symmetry.mode == SymmetryMode::Radial {
+                    frame.slider_int(im_str!("Folds"), &mut symmetry.folds, 2, 16).build();
+                }
+                if symmetry.mode != SymmetryMode::Off {
+                    if frame.small_button(im_str!("Place Center")) {
+                        symmetry.placing = true;
+                    }
+                    if symmetry.placing {
+                        frame.same_line(0.0);
+                        frame.text("Click on the canvas");
+                    }
+                }
+            });
+
+        frame.window(im_str!("Pages"))
+            .flags(ImGuiWindowFlags::NoCollapse)
+            .position([0.0, 360.0], imgui_glfw_rs::imgui::Condition::FirstUseEver)
+            .size([300.0, 110.0], imgui_glfw_rs::imgui::Condition::FirstUseEver)
+            .build(|| {
+                if frame.is_window_hovered() || frame.is_window_focused() {
+                    is_hovered = true;
+                }
+
+                let mut selected = pages.current_index();
+                for i in 0..pages.len() {
+                    if i > 0 {
+                        frame.same_line(0.0);
+                    }
+                    frame.radio_button(&ImString::new((i + 1).to_string()), &mut selected, i);
+                }
+                if selected != pages.current_index() {
+                    pages.request(PageAction::Select(selected));
+                }
+
+                if frame.small_button(im_str!("Add")) {
+                    pages.request(PageAction::Add);
+                }
+                frame.same_line(0.0);
+                if frame.small_button(im_str!("Duplicate")) {
+                    pages.request(PageAction::Duplicate);
+                }
+                frame.same_line(0.0);
+                if frame.small_button(im_str!("Delete")) {
+                    pages.request(PageAction::Delete);
+                }
+                frame.same_line(0.0);
+                if frame.small_button(im_str!("Export All")) {
+                    pages.request(PageAction::ExportAll);
+                }
+                if let Some(keys) = keybindings.pages_label() {
+                    frame.text(format!("{} switches pages", keys));
+                }
+                if let Some(status) = &pages.status {
+                    frame.text(status);
+                }
             });
 
+        if !keybindings.problems.is_empty() {
+            frame.window(im_str!("Keybindings"))
+                .flags(ImGuiWindowFlags::NoCollapse)
+                .position([310.0, 0.0], imgui_glfw_rs::imgui::Condition::FirstUseEver)
+                .size([400.0, 110.0], imgui_glfw_rs::imgui::Condition::FirstUseEver)
+                .build(|| {
+                    if frame.is_window_hovered() || frame.is_window_focused() {
+                        is_hovered = true;
+                    }
+
+                    frame.text("Problems in keybindings.toml, defaults are used instead:");
+                    for problem in &keybindings.problems {
+                        frame.text(problem);
+                    }
+                });
+        }
+
+        if let Some(measurement) = measurement {
+            frame.tooltip_text(measurement);
+        }
 
         self.imgui_glfw.draw(frame, p_window);
         is_hovered
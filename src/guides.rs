@@ -0,0 +1,152 @@
+use glm::*;
+use crate::input::Input;
+use crate::open_gl::shaders::StandardShader;
+use crate::tools::Guides;
+
+// Keep in sync with ruler() in PIXEL_SHADER
+const RULER_WIDTH: f32 = 30.0;
+const RULER_HANDLE_SIZE: f32 = 8.0;
+// Strokes starting this close to the ruler's edge are drawn along it
+const RULER_SNAP_DISTANCE: f32 = 20.0;
+
+pub struct Grid {
+    pub enabled: bool,
+    pub spacing: i32,
+    pub color: Vec4,
+    pub snap: bool
+}
+
+impl Grid {
+    pub fn new() -> Self {
+        Grid { enabled: false, spacing: 40, color: vec4(1.0, 1.0, 1.0, 0.15), snap: true }
+    }
+
+    // Nearest grid intersection, if snapping is on
+    pub fn snap(&self, p: Vec2) -> Vec2 {
+        if !self.enabled || !self.snap || self.spacing <= 0 {
+            return p;
+        }
+        let spacing = self.spacing as f32;
+        vec2((p.x / spacing).round() * spacing, (p.y / spacing).round() * spacing)
+    }
+
+    pub fn bind(&self, shader: &StandardShader) {
+        shader.set_int(self.enabled as i32, "grid");
+        shader.set_int(Ord::max(self.spacing, 2), "grid_spacing");
+        shader.set_vector4(&self.color, "grid_color");
+    }
+}
+
+enum RulerDrag {
+    Start,
+    End,
+    Body(Vec2)
+}
+
+// A straight edge to draw along, the body sits on the left side of the edge going from start to end
+pub struct Ruler {
+    pub visible: bool,
+    pub start: Vec2,
+    pub end: Vec2,
+    drag: Option<RulerDrag>,
+    guiding: bool
+}
+
+impl Ruler {
+    pub fn new(center: Vec2) -> Self {
+        Ruler {
+            visible: false,
+            start: center - vec2(300.0, 0.0),
+            end: center + vec2(300.0, 0.0),
+            drag: None,
+            guiding: false
+        }
+    }
+
+    // Moves the ruler by its handles or body, returns true while it has the mouse
+    pub fn update(&mut self, input: &Input, cursor: Vec2) -> bool {
+        if !self.visible {
+            self.drag = None;
+            return false;
+        }
+
+        if input.get_button_down(1) {
+            let (along, across) = self.local(cursor);
+            self.drag = if length(cursor - self.start) < RULER_HANDLE_SIZE {
+                Some(RulerDrag::Start)
+            } else if length(cursor - self.end) < RULER_HANDLE_SIZE {
+                Some(RulerDrag::End)
+            } else if along >= 0.0 && along <= length(self.end - self.start) && across >= 0.0 && across <= RULER_WIDTH {
+                Some(RulerDrag::Body(cursor - self.start))
+            } else {
+                None
+            };
+        }
+        if !input.get_button(1) {
+            self.drag = None;
+        }
+
+        match self.drag {
+            Some(RulerDrag::Start) => self.start = cursor,
+            Some(RulerDrag::End) => self.end = cursor,
+            Some(RulerDrag::Body(grab)) => {
+                let dir = self.end - self.start;
+                self.start = cursor - grab;
+                self.end = self.start + dir;
+            }
+            None => return false
+        }
+        true
+    }
+
+    pub fn begin_stroke(&mut self, p: Vec2) {
+        let (_, across) = self.local(p);
+        self.guiding = self.visible && self.start != self.end && across.abs() < RULER_SNAP_DISTANCE;
+    }
+
+    // Projects the point onto the ruler's edge if the stroke started next to it
+    pub fn guide(&self, p: Vec2) -> Vec2 {
+        if !self.guiding || !self.visible {
+            return p;
+        }
+        let dir = self.end - self.start;
+        self.start + dir * (dot(p - self.start, dir) / dot(dir, dir))
+    }
+
+    pub fn bind(&self, shader: &StandardShader) {
+        shader.set_int(self.visible as i32, "ruler");
+        shader.set_vector2(&self.start, "ruler_start");
+        shader.set_vector2(&self.end, "ruler_end");
+    }
+
+    // Distance along the edge and away from it towards the body
+    fn local(&self, p: Vec2) -> (f32, f32) {
+        let len = length(self.end - self.start);
+        if len <= 0.0 {
+            return (0.0, length(p - self.start));
+        }
+        let dir = (self.end - self.start) / len;
+        let d = p - self.start;
+        (dot(d, dir), dot(d, vec2(-dir.y, dir.x)))
+    }
+}
+
+// What the tools follow while drawing, the ruler's edge and the grid
+pub struct StrokeGuides<'a> {
+    pub ruler: &'a mut Ruler,
+    pub grid: &'a Grid
+}
+
+impl Guides for StrokeGuides<'_> {
+    fn begin_stroke(&mut self, p: Vec2) {
+        self.ruler.begin_stroke(p);
+    }
+
+    fn guide(&self, p: Vec2) -> Vec2 {
+        self.ruler.guide(p)
+    }
+
+    fn snap(&self, p: Vec2) -> Vec2 {
+        self.grid.snap(p)
+    }
+}
@@ -1,50 +1,141 @@
-use crate::open_gl::texture::*;
-use std::collections::*;
+use std::collections::VecDeque;
+use crate::open_gl::shaders::ComputeShader;
+use crate::open_gl::texture::Texture2D;
+use crate::shader_code::DIFF_SHADER;
+use crate::tiles::{TileKey, Tiles, TILE_SIZE};
 
+thread_local! {
+    // GL objects belong to the thread that has the context, every history on it shares the one shader
+    static DIFF: ComputeShader = ComputeShader::new(DIFF_SHADER);
+    // Stands in for a tile that doesn't exist when comparing
+    static BLANK: Texture2D = {
+        let blank = Texture2D::new(TILE_SIZE, TILE_SIZE);
+        blank.clear();
+        blank
+    };
+}
+
+// A tile from the other side of an undo step, None where there was no tile
+struct Tile {
+    key: TileKey,
+    pixels: Option<Texture2D>
+}
+
+// The canvas and its undo steps. A step only keeps the tiles it changed, so memory grows with what was
+// drawn instead of with the size of the canvas
 pub struct History {
-    pub stack: VecDeque<Texture2D>,
+    current: Tiles,
+    undos: VecDeque<Vec<Tile>>,
+    redos: Vec<Vec<Tile>>,
     pub max_undos: i32,
-    undos: usize
+    // Counts steps, undos and redos, so savers can tell whether anything happened since they last looked
+    edits: u64
 }
 
 impl History {
-    pub fn new() -> Self {
-        History{ stack: VecDeque::new(), max_undos: 10, undos: 0 }
+    pub fn new(mut tiles: Tiles) -> Self {
+        tiles.track_changes();
+        History { current: tiles, undos: VecDeque::new(), redos: Vec::new(), max_undos: 10, edits: 0 }
     }
 
-    pub fn push_back(&mut self, texture: Texture2D) {
-        self.stack.push_back(texture);
+    // Starts an undo step from what's on the canvas now, whatever was undone can't be redone after this
+    pub fn begin_step(&mut self) {
+        self.commit();
+        self.redos.clear();
+        self.edits += 1;
+    }
 
-        if self.stack.len() > self.max_undos as usize + 1 {
-            self.stack.pop_front();
-        }
+    pub fn get_current(&self) -> &Tiles {
+        &self.current
     }
 
-    pub fn pop_back(&mut self) {
-        self.stack.pop_back();
+    // Throws away the changes made since the step began
+    pub fn revert(&mut self) {
+        for (key, pixels) in self.current.take_changes() {
+            self.current.replace(key, pixels);
+        }
+        self.edits += 1;
     }
 
-    pub fn pop_front(&mut self) {
-        self.stack.pop_front();
+    pub fn undo(&mut self) {
+        self.commit();
+        if let Some(mut step) = self.undos.pop_back() {
+            self.swap(&mut step);
+            self.redos.push(step);
+        }
+        self.edits += 1;
     }
 
-    pub fn get_current(&self) -> &Texture2D {
-        let index = glm::max(self.stack.len() as i32 - 1 - self.undos as i32, 0) as usize;
-        &self.stack[index]
+    pub fn redo(&mut self) {
+        self.commit();
+        if let Some(mut step) = self.redos.pop() {
+            self.swap(&mut step);
+            self.undos.push_back(step);
+        }
+        self.edits += 1;
     }
 
-    pub fn undo(&mut self) {
-        self.undos = glm::min(self.undos as i32 + 1, self.stack.len() as i32 - 1) as usize;
+    pub fn get_edits(&self) -> u64 {
+        self.edits
     }
 
-    pub fn redo(&mut self) {
-        self.undos = glm::max(self.undos as i32 - 1, 0) as usize;
+    // Turns the tiles written to since the step began into an undo step, keeping only those that really changed.
+    // A step that changed nothing is dropped
+    fn commit(&mut self) {
+        let changes = self.current.take_changes();
+        let after: Vec<_> = changes.iter().map(|(key, _)| self.current.get(*key)).collect();
+        let pairs: Vec<_> = changes.iter().zip(&after).map(|((_, before), after)| (before.as_ref(), after.as_deref())).collect();
+        let changed = differs(&pairs);
+        drop(after);
+
+        let step: Vec<Tile> = changes.into_iter().zip(changed).filter(|(_, changed)| *changed)
+            .map(|((key, pixels), _)| Tile { key, pixels }).collect();
+        if step.is_empty() {
+            return;
+        }
+        self.undos.push_back(step);
+        self.redos.clear();
+        while self.undos.len() > Ord::max(self.max_undos, 0) as usize {
+            self.undos.pop_front();
+        }
     }
 
-    pub fn pop_undos(&mut self) {
-        for _ in 0..self.undos {
-            self.stack.pop_back();
+    // Puts the step's tiles on the canvas and keeps what was there in the step instead
+    fn swap(&mut self, step: &mut [Tile]) {
+        for tile in step {
+            tile.pixels = self.current.replace(tile.key, tile.pixels.take());
         }
-        self.undos = 0;
     }
-}
\ No newline at end of file
+}
+
+// Whether the tiles of each pair differ, None being a blank tile. Compared on the GPU so only a texel per pair is read back
+pub fn differs(pairs: &[(Option<&Texture2D>, Option<&Texture2D>)]) -> Vec<bool> {
+    if pairs.is_empty() {
+        return Vec::new();
+    }
+    let flags = Texture2D::new(pairs.len() as u32, 1);
+    flags.clear();
+    DIFF.with(|diff_shader| BLANK.with(|blank| {
+        diff_shader.set_int(1, "before");
+        diff_shader.set_int(2, "after");
+        for (i, pair) in pairs.iter().enumerate() {
+            let (before, after) = match pair {
+                (None, None) => continue,
+                (before, after) => (before.unwrap_or(blank), after.unwrap_or(blank))
+            };
+            diff_shader.set_ivector2(&glm::vec2(i as f32, 0.0), "flag");
+            before.bind(gl::TEXTURE1);
+            after.bind(gl::TEXTURE2);
+            flags.bind_image(gl::TEXTURE0);
+            diff_shader.bind();
+            diff_shader.dispatch(TILE_SIZE.div_ceil(10), TILE_SIZE.div_ceil(10), 1);
+            diff_shader.wait();
+            diff_shader.unbind();
+            flags.unbind_image(gl::TEXTURE0);
+            after.unbind(gl::TEXTURE2);
+            before.unbind(gl::TEXTURE1);
+        }
+    }));
+
+    flags.read_pixels(0, 0, pairs.len() as u32, 1).chunks_exact(4).map(|flag| flag[0] > 0.0).collect()
+}
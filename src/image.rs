@@ -0,0 +1,60 @@
+use std::fs::File;
+use std::io::{BufWriter, Read, Write};
+use std::path::Path;
+
+// Loads a PNG as tightly packed RGBA floats with the bottom row first, like the canvas stores them
+pub fn load_png(path: &Path) -> Result<(u32, u32, Vec<f32>), String> {
+    let file = File::open(path).map_err(|error| error.to_string())?;
+    decode_png(file)
+}
+
+pub fn decode_png(source: impl Read) -> Result<(u32, u32, Vec<f32>), String> {
+    let mut decoder = png::Decoder::new(source);
+    // Palettes, transparency chunks and 16 bit channels all end up as 8 bit gray or RGB, with or without alpha
+    decoder.set_transformations(png::Transformations::normalize_to_color8());
+    let mut reader = decoder.read_info().map_err(|error| error.to_string())?;
+    let mut buffer = vec![0; reader.output_buffer_size()];
+    let info = reader.next_frame(&mut buffer).map_err(|error| error.to_string())?;
+
+    let channels = match info.color_type {
+        png::ColorType::Grayscale => 1,
+        png::ColorType::GrayscaleAlpha => 2,
+        png::ColorType::Rgb => 3,
+        png::ColorType::Rgba => 4,
+        png::ColorType::Indexed => return Err("unexpanded palette".to_string())
+    };
+
+    let (width, height) = (info.width, info.height);
+    let mut pixels = Vec::with_capacity((width * height * 4) as usize);
+    // PNG rows go top to bottom, the canvas goes bottom to top
+    for row in buffer[..info.buffer_size()].chunks_exact(info.line_size).rev() {
+        for pixel in row.chunks_exact(channels) {
+            let rgba = match channels {
+                1 => [pixel[0], pixel[0], pixel[0], 255],
+                2 => [pixel[0], pixel[0], pixel[0], pixel[1]],
+                3 => [pixel[0], pixel[1], pixel[2], 255],
+                _ => [pixel[0], pixel[1], pixel[2], pixel[3]]
+            };
+            pixels.extend(rgba.iter().map(|c| *c as f32 / 255.0));
+        }
+    }
+    Ok((width, height, pixels))
+}
+
+// Saves tightly packed RGBA floats with the bottom row first as an 8 bit PNG
+pub fn save_png(path: &Path, width: u32, height: u32, pixels: &[f32]) -> Result<(), String> {
+    let file = File::create(path).map_err(|error| error.to_string())?;
+    encode_png(BufWriter::new(file), width, height, pixels)
+}
+
+pub fn encode_png(target: impl Write, width: u32, height: u32, pixels: &[f32]) -> Result<(), String> {
+    let mut encoder = png::Encoder::new(target, width, height);
+    encoder.set_color(png::ColorType::Rgba);
+    encoder.set_depth(png::BitDepth::Eight);
+
+    let data: Vec<u8> = pixels.chunks_exact((width * 4) as usize).rev().flatten()
+        .map(|c| (c.clamp(0.0, 1.0) * 255.0).round() as u8)
+        .collect();
+    let mut writer = encoder.write_header().map_err(|error| error.to_string())?;
+    writer.write_image_data(&data).map_err(|error| error.to_string())
+}
@@ -8,8 +8,13 @@ pub struct Input {
 
     mouse: MouseState,
     prev_mouse: MouseState,
+    // Physical button behind each of the buttons the tools ask for
+    button_map: [usize; 4],
     prev_rel_cursor_pos: Vec2,
-    rel_cursor_pos: Vec2
+    rel_cursor_pos: Vec2,
+
+    scroll: Vec2,
+    pending_scroll: Vec2
 }
 
 impl Input {
@@ -23,8 +28,12 @@ impl Input {
 
             mouse: Default::default(),
             prev_mouse: Default::default(),
+            button_map: [0, 1, 2, 3],
             prev_rel_cursor_pos: vec2(0.0, 0.0),
-            rel_cursor_pos: vec2(0.0, 0.0)
+            rel_cursor_pos: vec2(0.0, 0.0),
+
+            scroll: vec2(0.0, 0.0),
+            pending_scroll: vec2(0.0, 0.0)
         }
     }
     pub fn poll(&mut self) {
@@ -33,6 +42,9 @@ impl Input {
 
         self.prev_mouse = self.mouse.clone();
         self.mouse = self.device_state.get_mouse();
+
+        self.scroll = self.pending_scroll;
+        self.pending_scroll = vec2(0.0, 0.0);
     }
     pub fn get_key(&self, key: Keycode) -> bool {
         self.keys.contains(&key)
@@ -55,35 +67,35 @@ impl Input {
     }
 
     pub fn get_button(&self, button: usize) -> bool{
-        self.mouse.button_pressed[button]
+        self.get_raw_button(self.map_button(button))
     }
     pub fn get_button_down(&self, button: usize) -> bool{
-        let button_state = if self.mouse.button_pressed.len() > button {
-            self.mouse.button_pressed[button]
-        } else {
-            false
-        };
-
-        let prev_button_state = if self.prev_mouse.button_pressed.len() > button {
-            self.prev_mouse.button_pressed[button]
-        } else {
-            false
-        };
-
-        if button_state && !prev_button_state {
-            return true;
-        }
-        return false;
+        self.get_raw_button_down(self.map_button(button))
     }
     pub fn get_button_up(&self, button: usize) -> bool{
-        let button_state = self.mouse.button_pressed[button];
-        let prev_button_state = self.prev_mouse.button_pressed[button];
+        let button = self.map_button(button);
+        !is_pressed(&self.mouse, button) && is_pressed(&self.prev_mouse, button)
+    }
 
-        if !button_state && prev_button_state {
-            return true;
-        }
-        return false;
+    // Physical buttons, without the remapping from the keybindings
+    pub fn get_raw_button(&self, button: usize) -> bool {
+        is_pressed(&self.mouse, button)
+    }
+    pub fn get_raw_button_down(&self, button: usize) -> bool {
+        is_pressed(&self.mouse, button) && !is_pressed(&self.prev_mouse, button)
     }
+
+    pub fn set_button_map(&mut self, button_map: [usize; 4]) {
+        self.button_map = button_map;
+    }
+
+    fn map_button(&self, button: usize) -> usize {
+        self.button_map.get(button).copied().unwrap_or(button)
+    }
+}
+
+fn is_pressed(mouse: &MouseState, button: usize) -> bool {
+    mouse.button_pressed.get(button).copied().unwrap_or(false)
 }
 
 impl Input {
@@ -98,4 +110,13 @@ impl Input {
     pub fn get_prev_cursor_pos(&self) -> Vec2 {
         self.prev_rel_cursor_pos
     }
-}
\ No newline at end of file
+
+    // Scroll events arrive through the window, they add up until the next poll
+    pub fn add_scroll(&mut self, x: f32, y: f32) {
+        self.pending_scroll = self.pending_scroll + vec2(x, y);
+    }
+
+    pub fn get_scroll(&self) -> Vec2 {
+        self.scroll
+    }
+}
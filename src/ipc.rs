@@ -0,0 +1,601 @@
+use std::io::{BufRead, BufReader, ErrorKind, Read, Write};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+use base64::Engine;
+use base64::engine::general_purpose::STANDARD as BASE64;
+use glm::*;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use crate::callout::Callout;
+use crate::cli::parse_color;
+use crate::brush::{draw_text, paint_polyline, MAX_BRUSH_SIZE};
+use crate::font;
+use crate::image::{encode_png, save_png};
+use crate::open_gl::shaders::ComputeShader;
+use crate::pages::{PageAction, Pages};
+use crate::project;
+use crate::selection::Selection;
+use crate::shapes::Shape;
+use crate::tiles::Surface;
+use crate::view::View;
+use crate::window::{OverlayMode, Win};
+
+#[cfg(unix)]
+use std::os::unix::net::{UnixListener, UnixStream};
+#[cfg(unix)]
+use crate::paths::runtime_dir;
+
+// Requests longer than this are answered with an error and the connection is closed
+const MAX_LINE: usize = 1 << 20;
+// A client that doesn't read any of its replies for this long is dropped
+const REPLY_TIMEOUT: Duration = Duration::from_secs(5);
+// How long ctl waits for the answer, overdraw answers at the end of the frame it got the request in
+const CTL_TIMEOUT: Duration = Duration::from_secs(10);
+const DEFAULT_TEXT_SCALE: u32 = 2;
+// Arrow heads grow with the line so they stay visible on thick lines
+const ARROW_HEAD: f32 = 12.0;
+const ARROW_ANGLE: f32 = 0.5;
+
+pub const CTL_USAGE: &str = "\
+Usage: overdraw ctl <COMMAND> [ARGS]
+
+Sends a command to the running overdraw and prints its reply.
+
+Commands:
+  toggle-edit          Show and focus the overlay, or hide it
+  toggle-presenting    Switch between presenting and edit mode
+  mode <MODE>          Switch to hidden, edit or presenting
+  clear                Clear the current page
+  undo                 Undo the last change on the current page
+  redo                 Redo the last undone change on the current page
+  color <COLOR>        Set the brush color, #RRGGBB or #RRGGBBAA
+  brush-size <PX>      Set the brush size in pixels
+  save <PATH>          Save the current page as PNG, or all pages if PATH ends in .overdraw
+  page <N>             Switch to page N, counted from 1
+  add-page             Add a blank page after the current one
+  status               Print the mode, page, color and brush size
+  line <X,Y> <X,Y>...  Draw a polyline through the points
+  rect <X,Y> <X,Y>     Draw a rectangle between two corners
+  ellipse <X,Y> <X,Y>  Draw an ellipse inside the box between two corners
+  arrow <X,Y> <X,Y>    Draw an arrow from the first point to the second
+  text <X,Y> <TEXT>    Draw text with its top left corner at the point
+  canvas-png <PATH>    Write the current page as PNG on this side of the socket
+  raw <JSON>           Send a request as is
+
+Drawing commands take --color <COLOR> and --width <PX>, text takes --scale <N> instead of
+--width. They default to the current color and brush size. Points are window pixels
+with the origin at the top left, like a screenshot of the overlay.
+";
+
+// One JSON object per line, for example {\"command\": \"set_color\", \"color\": \"#ff0000\"}
+#[derive(Serialize, Deserialize, Clone, Debug)]
+#[serde(tag = "command", rename_all = "snake_case")]
+pub enum Request {
+    ToggleEdit,
+    TogglePresenting,
+    SetMode { mode: OverlayMode },
+    Clear,
+    Undo,
+    Redo,
+    SetColor { color: String },
+    SetBrushSize { size: i32 },
+    Save { path: PathBuf },
+    Page { index: usize },
+    AddPage,
+    Status,
+    // Every drawing command is one undo step, points are [x, y] in window pixels from the top left
+    Polyline { points: Vec<[f32; 2]>, color: Option<String>, width: Option<i32> },
+    Rect { from: [f32; 2], to: [f32; 2], color: Option<String>, width: Option<i32> },
+    Ellipse { from: [f32; 2], to: [f32; 2], color: Option<String>, width: Option<i32> },
+    Arrow { from: [f32; 2], to: [f32; 2], color: Option<String>, width: Option<i32> },
+    Text { position: [f32; 2], text: String, color: Option<String>, scale: Option<u32> },
+    // The current page as {"width", "height", "png"} with the PNG base64 encoded
+    CanvasPng
+}
+
+// Every reply is one line, {"ok": true} with an optional "result", or {"ok": false, "error": "..."}
+pub type Reply = Result<Value, String>;
+
+fn encode_reply(reply: Reply) -> String {
+    match reply {
+        Ok(Value::Null) => json!({ "ok": true }),
+        Ok(result) => json!({ "ok": true, "result": result }),
+        Err(error) => json!({ "ok": false, "error": error })
+    }.to_string()
+}
+
+#[cfg(unix)]
+pub fn socket_path() -> Result<PathBuf, String> {
+    Ok(runtime_dir()?.join("overdraw.sock"))
+}
+
+#[cfg(unix)]
+struct Client {
+    id: u64,
+    stream: UnixStream,
+    buffer: Vec<u8>,
+    // Replies waiting for the client to read them, written as far as the socket takes them every frame
+    outgoing: Vec<u8>,
+    // Since when the client hasn't taken any of its replies
+    stalled_since: Option<Instant>,
+    // Sent everything it's going to send, it still gets its replies
+    hung_up: bool,
+    closed: bool
+}
+
+#[cfg(unix)]
+impl Client {
+    fn flush(&mut self) {
+        while !self.outgoing.is_empty() && !self.closed {
+            match self.stream.write(&self.outgoing) {
+                Ok(0) => self.closed = true,
+                Ok(written) => {
+                    self.outgoing.drain(..written);
+                    self.stalled_since = None;
+                }
+                Err(error) if error.kind() == ErrorKind::Interrupted => {}
+                Err(error) if error.kind() == ErrorKind::WouldBlock => {
+                    self.closed |= self.stalled_since.get_or_insert_with(Instant::now).elapsed() > REPLY_TIMEOUT;
+                    break;
+                }
+                Err(_) => self.closed = true
+            }
+        }
+    }
+}
+
+// Polled once per frame, never blocks the main loop while waiting for clients
+#[cfg(unix)]
+pub struct Server {
+    path: PathBuf,
+    listener: UnixListener,
+    clients: Vec<Client>,
+    next_id: u64
+}
+
+#[cfg(unix)]
+impl Server {
+    pub fn start() -> Result<Self, String> {
+        let path = socket_path()?;
+        if path.exists() {
+            // A socket nobody answers on is left over from a crash
+            if UnixStream::connect(&path).is_ok() {
+                return Err(format!("another overdraw is already listening on {}", path.display()));
+            }
+            std::fs::remove_file(&path).map_err(|error| error.to_string())?;
+        }
+        // Anyone who can connect can draw on the screen, the directory keeps everyone but the user out
+        let listener = UnixListener::bind(&path).map_err(|error| format!("{}: {}", path.display(), error))?;
+        listener.set_nonblocking(true).map_err(|error| error.to_string())?;
+        Ok(Server { path, listener, clients: Vec::new(), next_id: 0 })
+    }
+
+    // Returns the complete lines received since the last call, tagged with the client to answer.
+    // A client that hasn't read its earlier replies yet isn't listened to until it did
+    pub fn poll(&mut self) -> Vec<(u64, Result<Request, String>)> {
+        while let Ok((stream, _)) = self.listener.accept() {
+            if stream.set_nonblocking(true).is_ok() {
+                self.clients.push(Client { id: self.next_id, stream, buffer: Vec::new(), outgoing: Vec::new(), stalled_since: None, hung_up: false, closed: false });
+                self.next_id += 1;
+            }
+        }
+
+        let mut requests = Vec::new();
+        for client in &mut self.clients {
+            client.flush();
+            if !client.outgoing.is_empty() || client.hung_up || client.closed {
+                continue;
+            }
+
+            // Stops at a line's worth, the rest stays in the socket for the next frame
+            let mut chunk = [0; 4096];
+            while client.buffer.len() <= MAX_LINE {
+                match client.stream.read(&mut chunk) {
+                    Ok(0) => {
+                        client.hung_up = true;
+                        break;
+                    }
+                    Ok(read) => client.buffer.extend_from_slice(&chunk[..read]),
+                    Err(error) if error.kind() == ErrorKind::Interrupted => {}
+                    Err(error) => {
+                        client.closed |= error.kind() != ErrorKind::WouldBlock;
+                        break;
+                    }
+                }
+            }
+
+            while let Some(end) = client.buffer.iter().position(|byte| *byte == b'\n') {
+                let line: Vec<u8> = client.buffer.drain(..=end).collect();
+                let line = String::from_utf8_lossy(&line);
+                if !line.trim().is_empty() {
+                    requests.push((client.id, serde_json::from_str(line.trim()).map_err(|error| format!("invalid request: {}", error))));
+                }
+            }
+            if client.buffer.len() > MAX_LINE {
+                client.buffer.clear();
+                client.hung_up = true;
+                requests.push((client.id, Err(format!("request is longer than {} bytes", MAX_LINE))));
+            }
+        }
+        requests
+    }
+
+    // Queued and written as far as the socket takes it, the rest goes out over the next frames
+    pub fn respond(&mut self, id: u64, reply: Reply) {
+        let Some(client) = self.clients.iter_mut().find(|client| client.id == id) else {
+            return;
+        };
+        client.outgoing.extend_from_slice(encode_reply(reply).as_bytes());
+        client.outgoing.push(b'\n');
+        client.flush();
+    }
+
+    // A client that hung up is kept until it got all of its replies
+    pub fn drop_closed(&mut self) {
+        self.clients.retain(|client| !(client.closed || client.hung_up && client.outgoing.is_empty()));
+    }
+}
+
+#[cfg(unix)]
+impl Drop for Server {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_file(&self.path);
+    }
+}
+
+#[cfg(not(unix))]
+pub struct Server;
+
+#[cfg(not(unix))]
+impl Server {
+    pub fn start() -> Result<Self, String> {
+        Err("the control socket needs Unix domain sockets".to_string())
+    }
+
+    pub fn poll(&mut self) -> Vec<(u64, Result<Request, String>)> {
+        Vec::new()
+    }
+
+    pub fn respond(&mut self, _id: u64, _reply: Reply) {}
+
+    pub fn drop_closed(&mut self) {}
+}
+
+// Everything a request may touch, color and brush_size are the ones the GUI shows
+pub struct Context<'a> {
+    pub window: &'a mut Win,
+    pub view: &'a View,
+    pub pages: &'a mut Pages,
+    pub selection: &'a mut Selection,
+    pub callout: &'a mut Callout,
+    pub compute_shader: &'a ComputeShader,
+    pub stamp_shader: &'a ComputeShader,
+    pub color: &'a mut Vec4,
+    pub brush_size: &'a mut i32
+}
+
+// Whatever is floating gets put down first, the same as before switching pages
+pub fn handle(request: Request, context: Context) -> Reply {
+    let Context { window, view, pages, selection, callout, compute_shader, stamp_shader, color, brush_size } = context;
+    // Same mapping as the cursor, so a point lands where it appears on screen whatever the view
+    let height = window.get_size().y;
+    let to_canvas = |point: [f32; 2]| view.to_canvas(vec2(point[0], height - point[1]));
+    let stroke = |text: Option<String>, width: Option<i32>| -> Result<(Vec4, i32), String> {
+        let stroke_color = text.map_or(Ok(*color), |text| parse_color(&text))?;
+        let stroke_width = width.unwrap_or(*brush_size);
+        if !(1..=MAX_BRUSH_SIZE).contains(&stroke_width) {
+            return Err(format!("invalid width {}, expected 1 to {}", stroke_width, MAX_BRUSH_SIZE));
+        }
+        Ok((stroke_color, stroke_width))
+    };
+
+    match request {
+        Request::ToggleEdit => {
+            window.set_mode(if window.get_mode() == OverlayMode::Hidden { OverlayMode::Edit } else { OverlayMode::Hidden });
+        }
+        Request::TogglePresenting => {
+            window.set_mode(if window.get_mode() == OverlayMode::Presenting { OverlayMode::Edit } else { OverlayMode::Presenting });
+        }
+        Request::SetMode { mode } => window.set_mode(mode),
+        Request::Clear => {
+            selection.confirm(pages.current(), stamp_shader);
+            callout.commit(pages.current_mut());
+            let history = pages.current_mut();
+            history.begin_step();
+            history.get_current().clear();
+        }
+        Request::Undo => {
+            selection.discard();
+            pages.current_mut().undo();
+        }
+        Request::Redo => {
+            selection.discard();
+            pages.current_mut().redo();
+        }
+        Request::SetColor { color: text } => *color = parse_color(&text)?,
+        Request::SetBrushSize { size } => {
+            if !(1..=MAX_BRUSH_SIZE).contains(&size) {
+                return Err(format!("invalid brush size {}, expected 1 to {}", size, MAX_BRUSH_SIZE));
+            }
+            *brush_size = size;
+        }
+        Request::Save { path } => {
+            selection.confirm(pages.current(), stamp_shader);
+            callout.commit(pages.current_mut());
+            save(pages, &path)?;
+        }
+        Request::Page { index } => {
+            if index < 1 || index > pages.len() {
+                return Err(format!("there is no page {}, pages go from 1 to {}", index, pages.len()));
+            }
+            selection.confirm(pages.current(), stamp_shader);
+            callout.commit(pages.current_mut());
+            pages.apply(PageAction::Select(index - 1));
+        }
+        Request::AddPage => {
+            selection.confirm(pages.current(), stamp_shader);
+            callout.commit(pages.current_mut());
+            pages.apply(PageAction::Add);
+        }
+        Request::Status => {
+            let channels = color.as_array().map(|c| (c.clamp(0.0, 1.0) * 255.0).round() as u8);
+            return Ok(json!({
+                "mode": window.get_mode(),
+                "page": pages.current_index() + 1,
+                "pages": pages.len(),
+                "color": format!("#{:02x}{:02x}{:02x}{:02x}", channels[0], channels[1], channels[2], channels[3]),
+                "brush_size": *brush_size
+            }));
+        }
+        Request::Polyline { points, color, width } => {
+            if points.len() < 2 {
+                return Err("a polyline needs at least two points".to_string());
+            }
+            let (color, width) = stroke(color, width)?;
+            let points: Vec<Vec2> = points.into_iter().map(to_canvas).collect();
+            draw(pages, selection, callout, stamp_shader, |surface| paint_polyline(compute_shader, surface, color, &points, width));
+        }
+        Request::Rect { from, to, color, width } => {
+            let (color, width) = stroke(color, width)?;
+            let (a, b) = (to_canvas(from), to_canvas(to));
+            let outline = Shape::Rectangle(min(a, b), max(a, b)).outline();
+            draw(pages, selection, callout, stamp_shader, |surface| paint_polyline(compute_shader, surface, color, &outline, width));
+        }
+        Request::Ellipse { from, to, color, width } => {
+            let (color, width) = stroke(color, width)?;
+            let (a, b) = (to_canvas(from), to_canvas(to));
+            let outline = Shape::Ellipse((a + b) * 0.5, abs(b - a) * 0.5).outline();
+            draw(pages, selection, callout, stamp_shader, |surface| paint_polyline(compute_shader, surface, color, &outline, width));
+        }
+        Request::Arrow { from, to, color, width } => {
+            let (color, width) = stroke(color, width)?;
+            let (start, end) = (to_canvas(from), to_canvas(to));
+            if start == end {
+                return Err("an arrow needs two different points".to_string());
+            }
+            let head = arrow_head(start, end, ARROW_HEAD.max(width as f32 * 4.0));
+            draw(pages, selection, callout, stamp_shader, |surface| {
+                paint_polyline(compute_shader, surface, color, &[start, end], width);
+                paint_polyline(compute_shader, surface, color, &head, width);
+            });
+        }
+        Request::Text { position, text, color, scale } => {
+            let (color, _) = stroke(color, None)?;
+            let scale = scale.unwrap_or(DEFAULT_TEXT_SCALE).clamp(1, 16);
+            let (_, text_height) = font::measure(&text, scale);
+            let corner = to_canvas(position) - vec2(0.0, text_height as f32);
+            draw(pages, selection, callout, stamp_shader, |surface| draw_text(surface, &text, corner, scale, color));
+        }
+        Request::CanvasPng => {
+            selection.confirm(pages.current(), stamp_shader);
+            callout.commit(pages.current_mut());
+            let (width, height, pixels) = pages.image();
+            let mut png = Vec::new();
+            encode_png(&mut png, width, height, &pixels)?;
+            return Ok(json!({ "width": width, "height": height, "png": BASE64.encode(png) }));
+        }
+    }
+    Ok(Value::Null)
+}
+
+// One new history entry per request, like a stroke made by hand
+fn draw(pages: &mut Pages, selection: &mut Selection, callout: &mut Callout, stamp_shader: &ComputeShader, paint: impl FnOnce(&dyn Surface)) {
+    selection.confirm(pages.current(), stamp_shader);
+    callout.commit(pages.current_mut());
+    let history = pages.current_mut();
+    history.begin_step();
+    paint(history.get_current());
+}
+
+// The two barbs at the end of an arrow as one polyline through its tip
+fn arrow_head(start: Vec2, end: Vec2, size: f32) -> [Vec2; 3] {
+    let back = normalize(start - end) * size;
+    let rotate = |v: Vec2, angle: f32| vec2(v.x * angle.cos() - v.y * angle.sin(), v.x * angle.sin() + v.y * angle.cos());
+    [end + rotate(back, ARROW_ANGLE), end, end + rotate(back, -ARROW_ANGLE)]
+}
+
+fn save(pages: &Pages, path: &Path) -> Result<(), String> {
+    if path.extension().is_some_and(|extension| extension == project::EXTENSION) {
+        return pages.to_project().save(path);
+    }
+    let (width, height, pixels) = pages.image();
+    save_png(path, width, height, &pixels)
+}
+
+// Turns `overdraw ctl` arguments into a request line, and for canvas-png the file the answer goes to
+fn parse_ctl(args: &[String]) -> Result<(String, Option<PathBuf>), String> {
+    let (command, rest) = args.split_first().ok_or("missing command")?;
+    if matches!(command.as_str(), "line" | "rect" | "ellipse" | "arrow" | "text") {
+        let request = parse_draw(command, rest)?;
+        return Ok((serde_json::to_string(&request).map_err(|error| error.to_string())?, None));
+    }
+    let argument = |name: &str| match rest {
+        [value] => Ok(value.clone()),
+        _ => Err(format!("{} takes exactly one argument, {}", command, name))
+    };
+    let no_arguments = || if rest.is_empty() { Ok(()) } else { Err(format!("{} doesn't take arguments", command)) };
+
+    let request = match command.as_str() {
+        "raw" => return Ok((argument("the JSON request")?, None)),
+        "toggle-edit" => no_arguments().map(|_| Request::ToggleEdit)?,
+        "toggle-presenting" => no_arguments().map(|_| Request::TogglePresenting)?,
+        "mode" => {
+            let mode = argument("hidden, edit or presenting")?;
+            Request::SetMode { mode: serde_json::from_value(Value::String(mode.clone())).map_err(|_| format!("unknown mode '{}', expected hidden, edit or presenting", mode))? }
+        }
+        "clear" => no_arguments().map(|_| Request::Clear)?,
+        "undo" => no_arguments().map(|_| Request::Undo)?,
+        "redo" => no_arguments().map(|_| Request::Redo)?,
+        "color" => Request::SetColor { color: argument("the color")? },
+        "brush-size" => {
+            let size = argument("the size in pixels")?;
+            Request::SetBrushSize { size: size.parse().map_err(|_| format!("invalid brush size '{}'", size))? }
+        }
+        // The server runs somewhere else, so relative paths are resolved here
+        "save" => Request::Save { path: absolute(PathBuf::from(argument("the path")?))? },
+        "page" => {
+            let index = argument("the page number")?;
+            Request::Page { index: index.parse().map_err(|_| format!("invalid page '{}'", index))? }
+        }
+        "add-page" => no_arguments().map(|_| Request::AddPage)?,
+        "status" => no_arguments().map(|_| Request::Status)?,
+        "canvas-png" => {
+            let output = PathBuf::from(argument("the path")?);
+            return Ok((serde_json::to_string(&Request::CanvasPng).map_err(|error| error.to_string())?, Some(output)));
+        }
+        _ => return Err(format!("unknown command '{}'", command))
+    };
+    Ok((serde_json::to_string(&request).map_err(|error| error.to_string())?, None))
+}
+
+fn absolute(path: PathBuf) -> Result<PathBuf, String> {
+    if path.is_absolute() {
+        return Ok(path);
+    }
+    Ok(std::env::current_dir().map_err(|error| error.to_string())?.join(path))
+}
+
+// Points are X,Y, the options can go anywhere between them
+fn parse_draw(command: &str, args: &[String]) -> Result<Request, String> {
+    let (mut color, mut width, mut scale) = (None, None, None);
+    let mut positional = Vec::new();
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        let mut value = || args.next().cloned().ok_or(format!("{} needs a value", arg));
+        match arg.as_str() {
+            "--color" => color = Some(value()?),
+            "--width" if command != "text" => {
+                let text = value()?;
+                width = Some(text.parse().map_err(|_| format!("invalid width '{}'", text))?);
+            }
+            "--scale" if command == "text" => {
+                let text = value()?;
+                scale = Some(text.parse().map_err(|_| format!("invalid scale '{}'", text))?);
+            }
+            _ if arg.starts_with("--") => return Err(format!("{} doesn't take {}", command, arg)),
+            _ => positional.push(arg.as_str())
+        }
+    }
+
+    let point = |text: &str| -> Result<[f32; 2], String> {
+        let invalid = || format!("invalid point '{}', expected X,Y", text);
+        let (x, y) = text.split_once(',').ok_or_else(invalid)?;
+        Ok([x.trim().parse().map_err(|_| invalid())?, y.trim().parse().map_err(|_| invalid())?])
+    };
+    let corners = || match positional[..] {
+        [from, to] => Ok((point(from)?, point(to)?)),
+        _ => Err(format!("{} takes two points", command))
+    };
+
+    Ok(match command {
+        "line" => {
+            if positional.len() < 2 {
+                return Err("line takes at least two points".to_string());
+            }
+            Request::Polyline { points: positional.iter().map(|text| point(text)).collect::<Result<_, _>>()?, color, width }
+        }
+        "rect" => {
+            let (from, to) = corners()?;
+            Request::Rect { from, to, color, width }
+        }
+        "ellipse" => {
+            let (from, to) = corners()?;
+            Request::Ellipse { from, to, color, width }
+        }
+        "arrow" => {
+            let (from, to) = corners()?;
+            Request::Arrow { from, to, color, width }
+        }
+        _ => match positional[..] {
+            [position, text] => Request::Text { position: point(position)?, text: text.to_string(), color, scale },
+            _ => return Err("text takes a point and the text, quote it if it has spaces".to_string())
+        }
+    })
+}
+
+// Exit code for the process, 0 when the request went through
+#[cfg(unix)]
+pub fn run_ctl(args: &[String]) -> i32 {
+    if args.first().is_some_and(|arg| arg == "-h" || arg == "--help") {
+        print!("{}", CTL_USAGE);
+        return 0;
+    }
+    let (line, output) = match parse_ctl(args) {
+        Ok(parsed) => parsed,
+        Err(error) => {
+            eprintln!("overdraw ctl: {}\nTry 'overdraw ctl --help' for more information.", error);
+            return 2;
+        }
+    };
+
+    let reply = socket_path()
+        .and_then(|path| UnixStream::connect(&path).map_err(|error| format!("can't reach overdraw on {}: {}", path.display(), error)))
+        .and_then(|mut stream| {
+            stream.set_read_timeout(Some(CTL_TIMEOUT)).map_err(|error| error.to_string())?;
+            stream.write_all(format!("{}\n", line).as_bytes()).map_err(|error| error.to_string())?;
+            let mut reply = String::new();
+            BufReader::new(stream).read_line(&mut reply).map_err(|error| match error.kind() {
+                ErrorKind::WouldBlock | ErrorKind::TimedOut => format!("overdraw didn't answer within {} seconds", CTL_TIMEOUT.as_secs()),
+                _ => error.to_string()
+            })?;
+            serde_json::from_str::<Value>(&reply).map_err(|_| "overdraw closed the connection without answering".to_string())
+        });
+
+    match reply {
+        Ok(reply) if reply["ok"] == true && output.is_some() => {
+            let output = output.unwrap();
+            let written = reply["result"]["png"].as_str().ok_or("the answer has no PNG".to_string())
+                .and_then(|png| BASE64.decode(png).map_err(|error| error.to_string()))
+                .and_then(|png| std::fs::write(&output, png).map_err(|error| error.to_string()));
+            match written {
+                Ok(()) => 0,
+                Err(error) => {
+                    eprintln!("overdraw ctl: can't write {}: {}", output.display(), error);
+                    1
+                }
+            }
+        }
+        Ok(reply) if reply["ok"] == true => {
+            match &reply["result"] {
+                Value::Null => {}
+                Value::String(text) => println!("{}", text),
+                result => println!("{}", serde_json::to_string_pretty(result).unwrap_or_default())
+            }
+            0
+        }
+        Ok(reply) => {
+            eprintln!("overdraw ctl: {}", reply["error"].as_str().unwrap_or("request failed"));
+            1
+        }
+        Err(error) => {
+            eprintln!("overdraw ctl: {}", error);
+            1
+        }
+    }
+}
+
+#[cfg(not(unix))]
+pub fn run_ctl(_args: &[String]) -> i32 {
+    eprintln!("overdraw ctl: the control socket needs Unix domain sockets");
+    1
+}
@@ -0,0 +1,472 @@
+use std::collections::HashMap;
+use std::fmt;
+use std::fs;
+use std::str::FromStr;
+use device_query::Keycode;
+use serde::Deserialize;
+use crate::input::Input;
+use crate::pages::MAX_SHORTCUT_PAGES;
+use crate::paths::config_dir;
+use crate::tool::ToolKind;
+
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub enum Action {
+    ToggleEdit,
+    TogglePresenting,
+    Undo,
+    Redo,
+    ToggleBackground,
+    // Mouse buttons the tools read as buttons 1, 2 and 3
+    Paint,
+    Erase,
+    Pan,
+    // Held while using the paint button
+    PanHold,
+    ResizeBrush,
+    StraightLine,
+    // Held while scrolling
+    Zoom,
+    ResetCounter,
+    Page(usize)
+}
+
+// Name in the config file, the action and its default binding
+fn defaults() -> Vec<(String, Action, &'static str)> {
+    let mut defaults = vec![
+        ("toggle_edit".to_string(), Action::ToggleEdit, "LCtrl+LAlt+A"),
+        ("toggle_presenting".to_string(), Action::TogglePresenting, "LCtrl+LAlt+P"),
+        ("undo".to_string(), Action::Undo, "LCtrl+Z"),
+        ("redo".to_string(), Action::Redo, "LCtrl+Y"),
+        ("toggle_background".to_string(), Action::ToggleBackground, "LCtrl+B"),
+        ("paint".to_string(), Action::Paint, "Mouse1"),
+        ("erase".to_string(), Action::Erase, "Mouse2"),
+        ("pan".to_string(), Action::Pan, "Mouse3"),
+        ("pan_hold".to_string(), Action::PanHold, "Space"),
+        ("resize_brush".to_string(), Action::ResizeBrush, "LAlt"),
+        ("straight_line".to_string(), Action::StraightLine, "LShift"),
+        ("zoom".to_string(), Action::Zoom, "LCtrl"),
+        ("reset_counter".to_string(), Action::ResetCounter, "R")
+    ];
+    const PAGE_KEYS: [&str; MAX_SHORTCUT_PAGES] = ["LCtrl+LAlt+1", "LCtrl+LAlt+2", "LCtrl+LAlt+3", "LCtrl+LAlt+4", "LCtrl+LAlt+5", "LCtrl+LAlt+6", "LCtrl+LAlt+7", "LCtrl+LAlt+8", "LCtrl+LAlt+9"];
+    for (i, keys) in PAGE_KEYS.iter().enumerate() {
+        defaults.push((format!("page_{}", i + 1), Action::Page(i), keys));
+    }
+    defaults
+}
+
+const MODIFIERS: [Keycode; 8] = [Keycode::LControl, Keycode::RControl, Keycode::LAlt, Keycode::RAlt, Keycode::LShift, Keycode::RShift, Keycode::LMeta, Keycode::RMeta];
+
+const DEFAULT_HOLD_TOOLS: [(ToolKind, &str); 1] = [(ToolKind::Eyedropper, "I")];
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+enum Trigger {
+    Key(Keycode),
+    // Either side of a modifier
+    Either(Keycode, Keycode),
+    Mouse(usize)
+}
+
+impl Trigger {
+    fn parse(name: &str) -> Result<Self, String> {
+        let lower = name.to_ascii_lowercase();
+        let trigger = match lower.as_str() {
+            "ctrl" | "control" => Trigger::Either(Keycode::LControl, Keycode::RControl),
+            "lctrl" | "lcontrol" => Trigger::Key(Keycode::LControl),
+            "rctrl" | "rcontrol" => Trigger::Key(Keycode::RControl),
+            "alt" => Trigger::Either(Keycode::LAlt, Keycode::RAlt),
+            "lalt" => Trigger::Key(Keycode::LAlt),
+            "ralt" | "altgr" => Trigger::Key(Keycode::RAlt),
+            "shift" => Trigger::Either(Keycode::LShift, Keycode::RShift),
+            "lshift" => Trigger::Key(Keycode::LShift),
+            "rshift" => Trigger::Key(Keycode::RShift),
+            "super" | "meta" | "win" => Trigger::Either(Keycode::LMeta, Keycode::RMeta),
+            "lsuper" | "lmeta" | "lwin" => Trigger::Key(Keycode::LMeta),
+            "rsuper" | "rmeta" | "rwin" => Trigger::Key(Keycode::RMeta),
+            _ => {
+                if let Some(button) = lower.strip_prefix("mouse") {
+                    return match button.parse::<usize>() {
+                        Ok(button) if (1..=5).contains(&button) => Ok(Trigger::Mouse(button)),
+                        _ => Err(format!("unknown mouse button '{}', use Mouse1 to Mouse5", name))
+                    };
+                }
+                if name.len() == 1 && name.as_bytes()[0].is_ascii_digit() {
+                    return Ok(Trigger::Key(Keycode::from_str(&format!("Key{}", name))?));
+                }
+
+                // Keycode names are capitalized, but "escape" or "f1" should work too
+                let mut chars = name.chars();
+                let capitalized: String = chars.next().map(|first| first.to_uppercase().chain(chars).collect()).unwrap_or_default();
+                let key = Keycode::from_str(name).or_else(|_| Keycode::from_str(&capitalized)).map_err(|_| format!("unknown key '{}'", name))?;
+                Trigger::Key(key)
+            }
+        };
+        Ok(trigger)
+    }
+
+    fn is_held(&self, input: &Input) -> bool {
+        match *self {
+            Trigger::Key(key) => input.get_key(key),
+            Trigger::Either(left, right) => input.get_key(left) || input.get_key(right),
+            Trigger::Mouse(button) => input.get_raw_button(button)
+        }
+    }
+
+    fn covers(&self, key: Keycode) -> bool {
+        match *self {
+            Trigger::Key(own) => own == key,
+            Trigger::Either(left, right) => left == key || right == key,
+            Trigger::Mouse(_) => false
+        }
+    }
+
+    // How the GUI shows it, Display keeps the Keycode names the conflict messages use
+    fn label(&self) -> String {
+        match *self {
+            Trigger::Key(Keycode::LControl) => "LCtrl".to_string(),
+            Trigger::Key(Keycode::RControl) => "RCtrl".to_string(),
+            Trigger::Key(key) => {
+                let name = key.to_string();
+                name.strip_prefix("Key").filter(|digit| digit.len() == 1).map_or(name.clone(), str::to_string)
+            }
+            Trigger::Mouse(1) => "left mouse".to_string(),
+            Trigger::Mouse(2) => "right mouse".to_string(),
+            Trigger::Mouse(3) => "middle mouse".to_string(),
+            _ => self.to_string()
+        }
+    }
+
+    fn is_down(&self, input: &Input) -> bool {
+        match *self {
+            Trigger::Key(key) => input.get_key_down(key),
+            Trigger::Either(left, right) => input.get_key_down(left) || input.get_key_down(right),
+            Trigger::Mouse(button) => input.get_raw_button_down(button)
+        }
+    }
+}
+
+impl fmt::Display for Trigger {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match *self {
+            Trigger::Key(key) => write!(f, "{}", key),
+            Trigger::Either(Keycode::LControl, _) => write!(f, "Ctrl"),
+            Trigger::Either(Keycode::LAlt, _) => write!(f, "Alt"),
+            Trigger::Either(Keycode::LShift, _) => write!(f, "Shift"),
+            Trigger::Either(_, _) => write!(f, "Super"),
+            Trigger::Mouse(button) => write!(f, "Mouse{}", button)
+        }
+    }
+}
+
+// Any number of held keys or buttons followed by the one that triggers it, like "LCtrl+Shift+Z"
+#[derive(Clone, PartialEq, Eq, Debug)]
+struct Binding {
+    held: Vec<Trigger>,
+    trigger: Trigger
+}
+
+impl Binding {
+    fn parse(text: &str) -> Result<Self, String> {
+        let mut parts = text.split('+').map(str::trim).collect::<Vec<_>>();
+        if parts.iter().any(|part| part.is_empty()) {
+            return Err(format!("malformed binding '{}'", text));
+        }
+        let trigger = Trigger::parse(parts.pop().unwrap_or_default())?;
+        let held = parts.into_iter().map(Trigger::parse).collect::<Result<Vec<_>, _>>()?;
+        Ok(Binding { held, trigger })
+    }
+
+    fn is_held(&self, input: &Input) -> bool {
+        self.held.iter().all(|trigger| trigger.is_held(input)) && self.trigger.is_held(input)
+    }
+
+    // Extra modifiers keep it from firing, so LCtrl+Z doesn't go off for LCtrl+Shift+Z
+    fn is_pressed(&self, input: &Input) -> bool {
+        !self.extra_modifier(input) && self.held.iter().all(|trigger| trigger.is_held(input)) && self.trigger.is_down(input)
+    }
+
+    // Same for holding, so I picks the eyedropper but Ctrl+I doesn't
+    fn is_held_alone(&self, input: &Input) -> bool {
+        !self.extra_modifier(input) && self.is_held(input)
+    }
+
+    fn label(&self) -> String {
+        self.held.iter().chain([&self.trigger]).map(Trigger::label).collect::<Vec<_>>().join(" + ")
+    }
+
+    fn extra_modifier(&self, input: &Input) -> bool {
+        MODIFIERS.iter().any(|key| input.get_key(*key) && !self.held.iter().chain([&self.trigger]).any(|trigger| trigger.covers(*key)))
+    }
+
+    // Every set of physical keys that sets it off, in a fixed order so equal sets compare equal however they were
+    // written. Ctrl+Z gives LControl+Z and RControl+Z, so it overlaps with LCtrl+Z
+    fn combinations(&self) -> Vec<String> {
+        let mut combinations = vec![Vec::new()];
+        for trigger in self.held.iter().chain([&self.trigger]) {
+            let keys = match *trigger {
+                Trigger::Either(left, right) => vec![left.to_string(), right.to_string()],
+                _ => vec![trigger.to_string()]
+            };
+            combinations = combinations.into_iter().flat_map(|combination: Vec<String>| keys.iter().map(move |key| {
+                let mut combination = combination.clone();
+                combination.push(key.clone());
+                combination
+            })).collect();
+        }
+        combinations.into_iter().map(|mut keys| {
+            keys.sort();
+            keys.dedup();
+            keys.join("+")
+        }).collect()
+    }
+}
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum BindingList {
+    One(String),
+    Many(Vec<String>)
+}
+
+#[derive(Deserialize, Default)]
+#[serde(default)]
+struct KeybindingsFile {
+    actions: HashMap<String, BindingList>,
+    hold_tools: HashMap<String, BindingList>
+}
+
+pub struct Keybindings {
+    actions: HashMap<Action, Vec<Binding>>,
+    hold_tools: Vec<(ToolKind, Binding)>,
+    // Everything that was wrong with the config file, defaults are used in its place
+    pub problems: Vec<String>
+}
+
+impl Keybindings {
+    // Reads keybindings.toml from the config directory, anything it doesn't set keeps its default
+    pub fn load() -> Self {
+        let path = config_dir().join("keybindings.toml");
+        let mut problems = Vec::new();
+        let file = match fs::read_to_string(&path) {
+            Ok(text) => toml::from_str::<KeybindingsFile>(&text).unwrap_or_else(|error| {
+                problems.push(format!("{}: {}", path.display(), error));
+                KeybindingsFile::default()
+            }),
+            Err(_) => KeybindingsFile::default()
+        };
+
+        let keybindings = Keybindings::from_file(file, problems);
+        for problem in &keybindings.problems {
+            eprintln!("[ERROR::KEYBINDINGS] {}", problem);
+        }
+        keybindings
+    }
+
+    fn from_file(file: KeybindingsFile, problems: Vec<String>) -> Self {
+        let mut keybindings = Keybindings { actions: HashMap::new(), hold_tools: Vec::new(), problems };
+        let defaults = defaults();
+        for name in file.actions.keys().filter(|name| !defaults.iter().any(|(default, _, _)| default == *name)) {
+            keybindings.problems.push(format!("unknown action '{}'", name));
+        }
+        for (name, action, default) in &defaults {
+            let bindings = keybindings.parse_list(name, file.actions.get(name), default);
+            keybindings.actions.insert(*action, bindings);
+        }
+
+        for name in file.hold_tools.keys().filter(|name| ToolKind::from_name(name).is_none()) {
+            keybindings.problems.push(format!("unknown tool '{}'", name));
+        }
+        for tool in ToolKind::ALL {
+            let default = DEFAULT_HOLD_TOOLS.iter().find(|(default, _)| *default == tool).map_or("", |(_, binding)| *binding);
+            for binding in keybindings.parse_list(tool.name(), file.hold_tools.get(tool.name()), default) {
+                keybindings.hold_tools.push((tool, binding));
+            }
+        }
+
+        keybindings.check_buttons(&defaults);
+        keybindings.check_conflicts(&defaults);
+        keybindings
+    }
+
+    pub fn pressed(&self, action: Action, input: &Input) -> bool {
+        self.actions.get(&action).map_or(false, |bindings| bindings.iter().any(|binding| binding.is_pressed(input)))
+    }
+
+    pub fn held(&self, action: Action, input: &Input) -> bool {
+        self.actions.get(&action).map_or(false, |bindings| bindings.iter().any(|binding| binding.is_held(input)))
+    }
+
+    // The tool that is held down right now, if any
+    pub fn held_tool(&self, input: &Input) -> Option<ToolKind> {
+        self.hold_tools.iter().find(|(_, binding)| binding.is_held_alone(input)).map(|(tool, _)| *tool)
+    }
+
+    // What the GUI hints show for an action, like "LCtrl + B", None when it's unbound
+    pub fn label(&self, action: Action) -> Option<String> {
+        labels(self.actions.get(&action).into_iter().flatten())
+    }
+
+    pub fn tool_label(&self, tool: ToolKind) -> Option<String> {
+        labels(self.hold_tools.iter().filter(|(own, _)| *own == tool).map(|(_, binding)| binding))
+    }
+
+    // "LCtrl + LAlt + 1-9" while the page keys are numbered like the defaults, otherwise the first and the last
+    pub fn pages_label(&self) -> Option<String> {
+        let first = self.actions.get(&Action::Page(0))?.first()?;
+        let numbered = (0..MAX_SHORTCUT_PAGES).all(|i| match self.actions.get(&Action::Page(i)).map(Vec::as_slice) {
+            Some([binding]) => binding.held == first.held && Ok(binding.trigger) == Keycode::from_str(&format!("Key{}", i + 1)).map(Trigger::Key),
+            _ => false
+        });
+        if numbered {
+            let held = first.held.iter().map(|trigger| format!("{} + ", trigger.label())).collect::<String>();
+            return Some(format!("{}1-{}", held, MAX_SHORTCUT_PAGES));
+        }
+        Some(format!("{} to {}", self.label(Action::Page(0))?, self.label(Action::Page(MAX_SHORTCUT_PAGES - 1))?))
+    }
+
+    // Physical mouse buttons for the paint, erase and pan buttons
+    pub fn buttons(&self) -> [usize; 4] {
+        let button = |action: Action, default: usize| match self.actions.get(&action).and_then(|bindings| bindings.first()) {
+            Some(Binding { trigger: Trigger::Mouse(button), .. }) => *button,
+            _ => default
+        };
+        [0, button(Action::Paint, 1), button(Action::Erase, 2), button(Action::Pan, 3)]
+    }
+
+    fn parse_list(&mut self, name: &str, list: Option<&BindingList>, default: &str) -> Vec<Binding> {
+        let texts = match list {
+            Some(BindingList::One(text)) => vec![text.clone()],
+            Some(BindingList::Many(texts)) => texts.clone(),
+            None => return Binding::parse(default).into_iter().collect()
+        };
+        let mut bindings = Vec::new();
+        for text in &texts {
+            match Binding::parse(text) {
+                Ok(binding) => bindings.push(binding),
+                Err(error) => self.problems.push(format!("{}: {}", name, error))
+            }
+        }
+        // A typo shouldn't leave the action without a binding, an empty list unbinds it on purpose
+        if bindings.is_empty() && !texts.is_empty() {
+            bindings.extend(Binding::parse(default));
+        }
+        bindings
+    }
+
+    // The tools see these as plain mouse buttons, so they can't take modifiers
+    fn check_buttons(&mut self, defaults: &[(String, Action, &str)]) {
+        for (name, action, default) in defaults {
+            if !matches!(action, Action::Paint | Action::Erase | Action::Pan) {
+                continue;
+            }
+            let bindings = self.actions.get(action).cloned().unwrap_or_default();
+            if bindings.len() != 1 || !bindings[0].held.is_empty() || !matches!(bindings[0].trigger, Trigger::Mouse(_)) {
+                self.problems.push(format!("{} has to be a single mouse button, using {}", name, default));
+                self.actions.insert(*action, vec![Binding::parse(default).expect("default keybindings parse")]);
+            }
+        }
+    }
+
+    fn check_conflicts(&mut self, defaults: &[(String, Action, &str)]) {
+        let mut seen: HashMap<String, String> = HashMap::new();
+        let mut conflicts = Vec::new();
+        let actions = defaults.iter().flat_map(|(name, action, _)| self.actions[action].iter().map(move |binding| (name.clone(), binding)));
+        let tools = self.hold_tools.iter().map(|(tool, binding)| (format!("holding {}", tool.name()), binding));
+        for (name, binding) in actions.chain(tools) {
+            let combinations = binding.combinations();
+            // One report per binding, Ctrl+Shift+Z and Ctrl+Shift+Z overlap on four combinations
+            if let Some((keys, other)) = combinations.iter().find_map(|keys| seen.get(keys).map(|other| (keys, other))) {
+                conflicts.push(format!("{} and {} are both bound to {}", other, name, keys));
+            }
+            for keys in combinations {
+                seen.entry(keys).or_insert_with(|| name.clone());
+            }
+        }
+        self.problems.extend(conflicts);
+    }
+}
+
+fn labels<'a>(bindings: impl Iterator<Item = &'a Binding>) -> Option<String> {
+    let labels = bindings.map(Binding::label).collect::<Vec<_>>();
+    (!labels.is_empty()).then(|| labels.join(" or "))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn from_toml(text: &str) -> Keybindings {
+        Keybindings::from_file(toml::from_str(text).expect("valid toml"), Vec::new())
+    }
+
+    #[test]
+    fn parses_modifiers_keys_and_buttons() {
+        let binding = Binding::parse("LCtrl + shift+Z").unwrap();
+        assert_eq!(binding.held, vec![Trigger::Key(Keycode::LControl), Trigger::Either(Keycode::LShift, Keycode::RShift)]);
+        assert_eq!(binding.trigger, Trigger::Key(Keycode::Z));
+        assert_eq!(Binding::parse("Ctrl+Alt+1").unwrap().trigger, Trigger::Key(Keycode::Key1));
+        assert_eq!(Binding::parse("escape").unwrap().trigger, Trigger::Key(Keycode::Escape));
+        assert_eq!(Binding::parse("f1").unwrap().trigger, Trigger::Key(Keycode::F1));
+        assert_eq!(Binding::parse("Mouse3").unwrap().trigger, Trigger::Mouse(3));
+    }
+
+    #[test]
+    fn rejects_bad_names() {
+        assert_eq!(Binding::parse("Ctrl+ü"), Err("unknown key 'ü'".to_string()));
+        assert_eq!(Binding::parse("ßhift+A"), Err("unknown key 'ßhift'".to_string()));
+        assert_eq!(Binding::parse("Ctrl+Blorp"), Err("unknown key 'Blorp'".to_string()));
+        assert!(Binding::parse("Mouse6").is_err());
+        assert!(Binding::parse("Ctrl++").is_err());
+        assert!(Binding::parse("").is_err());
+    }
+
+    #[test]
+    fn bad_names_fall_back_to_the_default() {
+        let keybindings = from_toml("[actions]\nundo = \"Ctrl+ü\"");
+        assert_eq!(keybindings.actions[&Action::Undo], vec![Binding::parse("LCtrl+Z").unwrap()]);
+        assert_eq!(keybindings.problems, vec!["undo: unknown key 'ü'".to_string()]);
+    }
+
+    #[test]
+    fn defaults_dont_conflict() {
+        assert!(from_toml("").problems.is_empty());
+    }
+
+    #[test]
+    fn either_side_modifiers_overlap_with_one_side() {
+        let keybindings = from_toml("[actions]\nredo = \"Ctrl+Z\"");
+        assert_eq!(keybindings.problems, vec!["undo and redo are both bound to LControl+Z".to_string()]);
+    }
+
+    #[test]
+    fn order_doesnt_matter_for_conflicts() {
+        let keybindings = from_toml("[actions]\nundo = \"Shift+Ctrl+Z\"\nredo = \"Ctrl+Shift+Z\"");
+        assert_eq!(keybindings.problems.len(), 1);
+        assert!(keybindings.problems[0].starts_with("undo and redo are both bound to "));
+    }
+
+    #[test]
+    fn hold_tools_conflict_with_actions() {
+        let keybindings = from_toml("[hold_tools]\neyedropper = \"R\"");
+        assert_eq!(keybindings.problems, vec!["reset_counter and holding eyedropper are both bound to R".to_string()]);
+    }
+
+    #[test]
+    fn different_modifiers_dont_conflict() {
+        assert!(from_toml("[actions]\nundo = \"LCtrl+Z\"\nredo = \"RCtrl+Z\"").problems.is_empty());
+    }
+
+    #[test]
+    fn labels_follow_the_bindings() {
+        let keybindings = from_toml("");
+        assert_eq!(keybindings.label(Action::TogglePresenting), Some("LCtrl + LAlt + P".to_string()));
+        assert_eq!(keybindings.label(Action::Pan), Some("middle mouse".to_string()));
+        assert_eq!(keybindings.tool_label(ToolKind::Eyedropper), Some("I".to_string()));
+        assert_eq!(keybindings.tool_label(ToolKind::Fill), None);
+        assert_eq!(keybindings.pages_label(), Some("LCtrl + LAlt + 1-9".to_string()));
+
+        let keybindings = from_toml("[actions]\nstraight_line = [\"Shift\", \"X\"]\nreset_counter = []\npage_1 = \"F1\"\npage_9 = \"F9\"");
+        assert_eq!(keybindings.label(Action::StraightLine), Some("Shift or X".to_string()));
+        assert_eq!(keybindings.label(Action::ResetCounter), None);
+        assert_eq!(keybindings.pages_label(), Some("F1 to F9".to_string()));
+    }
+}
@@ -0,0 +1,75 @@
+use std::time::Instant;
+use glm::*;
+use crate::brush::*;
+use crate::open_gl::shaders::ComputeShader;
+use crate::open_gl::texture::Texture2D;
+
+struct LaserStroke {
+    points: Vec<Vec2>,
+    color: Vec4,
+    brush_size: i32,
+    finished: Option<Instant>
+}
+
+// Ink that fades away on its own, it lives on its own layer and never ends up in the history
+pub struct Laser {
+    pub enabled: bool,
+    pub duration: f32,
+    strokes: Vec<LaserStroke>,
+    texture: Texture2D,
+    dirty: bool
+}
+
+impl Laser {
+    pub fn new(width: u32, height: u32) -> Self {
+        Laser { enabled: false, duration: 2.0, strokes: Vec::new(), texture: Texture2D::new(width, height), dirty: false }
+    }
+
+    pub fn begin(&mut self, p: Vec2, color: Vec4, brush_size: i32) {
+        self.end();
+        self.strokes.push(LaserStroke { points: vec![p], color, brush_size, finished: None });
+    }
+
+    pub fn add_point(&mut self, p: Vec2) {
+        if let Some(stroke) = self.strokes.last_mut().filter(|stroke| stroke.finished.is_none()) {
+            if stroke.points.last() != Some(&p) {
+                stroke.points.push(p);
+            }
+        }
+    }
+
+    pub fn end(&mut self) {
+        if let Some(stroke) = self.strokes.last_mut().filter(|stroke| stroke.finished.is_none()) {
+            stroke.finished = Some(Instant::now());
+        }
+    }
+
+    // Redraws the strokes that are still alive with their current opacity
+    pub fn fade(&mut self, compute_shader: &ComputeShader) {
+        // Strokes stay fully visible for the first half of their lifetime and fade out over the second
+        let duration = self.duration;
+        let opacity = |stroke: &LaserStroke| match stroke.finished {
+            Some(finished) => (2.0 * (1.0 - finished.elapsed().as_secs_f32() / duration)).clamp(0.0, 1.0),
+            None => 1.0
+        };
+        self.strokes.retain(|stroke| opacity(stroke) > 0.0);
+
+        if !self.dirty && self.strokes.is_empty() {
+            return;
+        }
+        self.texture.clear();
+        self.dirty = !self.strokes.is_empty();
+
+        for stroke in &self.strokes {
+            let color = vec4(stroke.color.x, stroke.color.y, stroke.color.z, stroke.color.w * opacity(stroke));
+            if stroke.points.len() == 1 {
+                paint(compute_shader, &self.texture, color, stroke.points[0], stroke.points[0], stroke.brush_size);
+            }
+            paint_polyline(compute_shader, &self.texture, color, &stroke.points, stroke.brush_size);
+        }
+    }
+
+    pub fn get_texture(&self) -> &Texture2D {
+        &self.texture
+    }
+}
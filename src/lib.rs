@@ -0,0 +1,23 @@
+// The drawing engine: shaders, brushes, tiled pages with their history, projects and the tool plugin API. Canvas
+// wraps one page for drawing without a window, the overlay binary drives Pages and History itself and keeps its
+// built-in tools, input handling and GLFW and ImGui front end on its side. The only GUI in here is the ImGui Ui
+// tools get to draw their settings with
+pub mod open_gl;
+pub mod shader_code;
+pub mod canvas;
+pub mod brush;
+pub mod font;
+pub mod symmetry;
+pub mod history;
+pub mod pages;
+pub mod image;
+pub mod project;
+pub mod paths;
+pub mod shapes;
+pub mod tiles;
+pub mod tools;
+#[cfg(feature = "spray")]
+pub mod spray;
+
+pub use canvas::Canvas;
+pub use tools::{StrokeEvent, Tool, ToolContext, ToolRegistry};
@@ -4,159 +4,456 @@
 // include!(concat!(env!("OUT_DIR"), "/custom_bindings.rs"));
 
 mod input;
-mod open_gl;
 mod window;
-mod shader_code;
+mod passthrough;
 mod gui;
-mod history;
+mod view;
+mod laser;
+mod spotlight;
+mod fill;
+mod eyedropper;
+mod redact;
+mod gradient;
+mod guides;
+mod background;
+mod measure;
+mod stamp;
+mod callout;
+mod overlay;
+mod selection;
+mod tool;
+mod keybindings;
+mod settings;
+mod cli;
+mod ipc;
+mod collab;
+
+// The engine lives in the library, the front end's modules reach it through these as if it were still part of the binary
+use overdraw::{open_gl, shader_code, history, pages, image, brush, symmetry, font, paths, shapes, project, tiles, tools};
 
 use std::str::from_utf8;
 use shader_code::*;
 
 use std::time;
-use device_query::Keycode;
 use input::Input;
 
-use window::Win;
+use window::{OverlayMode, Win};
 
 use open_gl::*;
 use glm::*;
 use gui::*;
 
-use texture::*;
 use shaders::*;
 use buffers::Mesh;
-use crate::history::History;
+use texture::Texture2D;
+use crate::pages::{PageAction, Pages, MAX_SHORTCUT_PAGES};
+use crate::view::View;
+use crate::brush::*;
+use crate::guides::StrokeGuides;
+use crate::overlay::Overlay;
+use crate::selection::Selection;
+use crate::tool::{ToolKind, Toolbox};
+use crate::tools::ToolContext;
+use crate::keybindings::{Action, Keybindings};
+use crate::settings::Settings;
+use crate::project::Project;
+use crate::paths::set_config_dir;
+use crate::cli::{Command, SessionRole, USAGE};
+use crate::ipc::Server;
+use crate::collab::Session;
+
+// Seconds between checks whether the settings changed and need to be written
+const SETTINGS_SAVE_INTERVAL: f32 = 5.0;
+// Seconds between autosaves, only when a page changed and nothing is being dragged
+const AUTOSAVE_INTERVAL: f32 = 60.0;
 
 fn main() {
+    let options = match cli::parse(std::env::args().skip(1)) {
+        Ok(Command::Run(options)) => options,
+        Ok(Command::Help) => {
+            print!("{}", USAGE);
+            return;
+        }
+        Ok(Command::Version) => {
+            println!("overdraw {}", env!("CARGO_PKG_VERSION"));
+            return;
+        }
+        Ok(Command::Ctl(args)) => std::process::exit(ipc::run_ctl(&args)),
+        Err(error) => {
+            eprintln!("overdraw: {}\nTry 'overdraw --help' for more information.", error);
+            std::process::exit(2);
+        }
+    };
+    if let Some(config) = &options.config {
+        set_config_dir(config.clone());
+    }
+    // Everything that can go wrong with the arguments is reported before a window shows up
+    let project = options.open.as_ref().map(|path| Project::load(path).unwrap_or_else(|error| {
+        eprintln!("overdraw: can't open {}: {}", path.display(), error);
+        std::process::exit(1);
+    }));
+    let autosave = options.autosave.as_ref().map(|dir| {
+        if let Err(error) = std::fs::create_dir_all(dir) {
+            eprintln!("overdraw: can't use {} for autosaves: {}", dir.display(), error);
+            std::process::exit(1);
+        }
+        dir.join(format!("autosave.{}", project::EXTENSION))
+    });
+
+    let keybindings = Keybindings::load();
     let mut input = Input::new();
+    input.set_button_map(keybindings.buttons());
 
-    let mut window = Win::new();
+    let mut window = Win::new(options.monitor).unwrap_or_else(|error| {
+        eprintln!("overdraw: {}", error);
+        std::process::exit(1);
+    });
     viewport(0, 0, window.get_width(), window.get_height());
 
+    let name = options.name.clone().or_else(|| std::env::var("USER").or_else(|_| std::env::var("USERNAME")).ok()).unwrap_or_else(|| "overdraw".to_string());
+    let secret = options.secret.clone().unwrap_or_default();
+    let mut session = match &options.session {
+        Some(SessionRole::Host(address)) => Some(Session::host(address, &name, &secret, window.get_size().y)),
+        Some(SessionRole::Join(address)) => Some(Session::join(address, &name, &secret, window.get_size().y)),
+        None => None
+    }.transpose().unwrap_or_else(|error| {
+        eprintln!("overdraw: {}", error);
+        std::process::exit(1);
+    });
+
     let mut gui = Gui::new(&mut window.p_window);
 
     let mesh = Mesh::new();
     let shader = StandardShader::new(VERTEX_SHADER, PIXEL_SHADER);
 
-    let mut history = History::new();
-    history.push_back(Texture2D::new(window.get_width(), window.get_height()));
+    let mut pages = Pages::new(window.get_width(), window.get_height());
+    if let Some(project) = &project {
+        pages.load(project);
+    }
+    let mut view = View::new();
     let compute_shader = ComputeShader::new(COMPUTE_SHADER);
+    let stamp_shader = ComputeShader::new(STAMP_SHADER);
+    let redact_shader = ComputeShader::new(REDACT_SHADER);
+    let gradient_shader = ComputeShader::new(GRADIENT_SHADER);
+    let tile_shader = ComputeShader::new(TILE_SHADER);
 
-    let mut brush_size = 5;
-    let mut prev_brush_size = 5;
-    let mut prev_cursor_pos = input.get_cursor_pos();
+    // The tiles of the canvas and the overlay on top of them, drawn at screen size through the view every frame
+    let screen = Texture2D::new(window.get_width(), window.get_height());
+    let mut overlay = Overlay::new();
+    let mut selection = Selection::new();
+    let mut toolbox = Toolbox::new(window.get_width(), window.get_height());
+    #[cfg(feature = "dynamic-tools")]
+    for problem in toolbox.registry.load_plugins(&paths::config_dir().join("plugins"), &|name| window.p_glfw.get_proc_address_raw(name)) {
+        eprintln!("[ERROR::TOOLS::PLUGIN] {}", problem);
+    }
 
-    let mut color_picked = vec4(1.0, 1.0, 1.0, 1.0);
     let mut is_hovered = false;
-    // Only here because bug in input library
-    let mut first_click = false;
+
+    let mut saved_settings = Settings::load();
+    saved_settings.apply(&mut toolbox, &mut pages);
+    // The command line wins over whatever was saved last time
+    if let Some(color) = options.color {
+        toolbox.color = color;
+    }
+    if let Some(size) = options.brush_size {
+        toolbox.brush_size = size;
+    }
+    let mut last_save = time::Instant::now();
+    let mut saved_revision = pages.revision();
+    let mut last_autosave = time::Instant::now();
+    if options.visible {
+        window.set_mode(OverlayMode::Edit);
+    }
+    // Drawing still works without it, scripts just can't reach this instance
+    let mut server = Server::start().map_err(|error| eprintln!("[ERROR::IPC::START] {}", error)).ok();
     while window.open() {
         window.poll_events(&mut input, &mut gui.imgui, &mut gui.imgui_glfw);
         input.poll();
 
-        if !window.edit(&input) {
+        // Scripts are answered between frames, even while hidden so they can bring the overlay up
+        if let Some(server) = &mut server {
+            for (client, request) in server.poll() {
+                let reply = request.and_then(|request| ipc::handle(request, ipc::Context {
+                    window: &mut window,
+                    view: &view,
+                    pages: &mut pages,
+                    selection: &mut selection,
+                    callout: &mut toolbox.callout,
+                    compute_shader: &compute_shader,
+                    stamp_shader: &stamp_shader,
+                    color: &mut toolbox.color,
+                    brush_size: &mut toolbox.brush_size
+                }));
+                server.respond(client, reply);
+            }
+            server.drop_closed();
+        }
+
+        // Collaborators keep drawing while this one is hidden, their strokes are there when it comes back.
+        // What they finish waits while something is being drawn here
+        if let Some(current) = &mut session {
+            let busy = toolbox.registry.is_active() || selection.is_floating() || input.get_button(1) || input.get_button(2);
+            if let Err(error) = current.poll(&mut pages, &compute_shader, busy) {
+                eprintln!("[ERROR::COLLAB::SESSION] {}", error);
+                session = None;
+            }
+        }
+
+        let mode = window.edit(&input, &keybindings);
+        if mode == OverlayMode::Hidden {
+            if let Some(session) = &mut session {
+                session.send_cursor(None, toolbox.color);
+            }
             std::thread::sleep(time::Duration::from_millis(100));
             continue;
         }
+        // While presenting the drawing stays up but the mouse and keyboard belong to the apps below
+        let interactive = mode == OverlayMode::Edit;
+
+        // Pages
+        if interactive {
+            if let Some(index) = (0..MAX_SHORTCUT_PAGES).find(|i| keybindings.pressed(Action::Page(*i), &input)) {
+                pages.request(PageAction::Select(index));
+            }
+        }
+        if let Some(action) = pages.take_request() {
+            selection.confirm(pages.current(), &stamp_shader);
+            toolbox.callout.commit(pages.current_mut());
+            pages.apply(action);
+        }
+
+        // Whiteboard mode gets a canvas that can be panned and zoomed, over the desktop it stays put
+        let screen_coords = vec2(input.get_cursor_pos().x, window.get_size().y - input.get_cursor_pos().y);
+        let prev_screen_coords = vec2(input.get_prev_cursor_pos().x, window.get_size().y - input.get_prev_cursor_pos().y);
+        if !toolbox.background.enabled {
+            view.reset();
+        }
+        let page = pages.current_index();
+        let history = pages.current_mut();
 
         // Undo
-        if input.get_key(Keycode::LControl) {
-            if input.get_key_down(Keycode::Z) {
+        if interactive {
+            if keybindings.pressed(Action::Undo, &input) {
+                // The lifted pixels come back with the undo
+                selection.discard();
                 history.undo();
             }
         }
 
         // Redo
-        if input.get_key(Keycode::LControl) {
-            if input.get_key_down(Keycode::Y) {
+        if interactive {
+            if keybindings.pressed(Action::Redo, &input) {
                 history.redo();
             }
         }
 
+        // Board behind the ink
+        if interactive {
+            if keybindings.pressed(Action::ToggleBackground, &input) {
+                toolbox.background.enabled = !toolbox.background.enabled;
+            }
+        }
+
         // Compute
-        let mut cursor_coords = vec2(input.get_cursor_pos().x, window.get_size().y - input.get_cursor_pos().y);
-        let prev_cursor_coords = vec2(input.get_prev_cursor_pos().x, window.get_size().y - input.get_prev_cursor_pos().y);
-
-        if !is_hovered {
-            /////////////////////////////////////////////////////////////////////////////////////////////////////////////////
-            // This is only here because there is a bug in the input library with the first click, and I am too lazy to properly fix it
-            if !first_click && !input.get_key(Keycode::LAlt) && (input.get_button(1) || input.get_button(2)) {
-                history.pop_undos();
-                history.push_back(history.get_current().clone());
-                first_click = true;
+        let mut cursor_coords = view.to_canvas(screen_coords);
+        let prev_cursor_coords = view.to_canvas(prev_screen_coords);
+
+        if !toolbox.kind.is_selection() && selection.is_floating() {
+            selection.confirm(history, &stamp_shader);
+        }
+        if toolbox.kind != ToolKind::Callout {
+            toolbox.callout.commit(history);
+        }
+
+        overlay.clear();
+        // Holding a tool's key switches to it until it's let go, like I for the eyedropper
+        let active_tool = keybindings.held_tool(&input).unwrap_or(toolbox.kind);
+        let resizing = keybindings.held(Action::ResizeBrush, &input);
+        if !interactive {
+            toolbox.laser.end();
+        } else if toolbox.symmetry.placing && !is_hovered {
+            if input.get_button(1) {
+                toolbox.symmetry.center = cursor_coords;
+            } else if input.get_button_up(1) {
+                toolbox.symmetry.placing = false;
             }
-            /////////////////////////////////////////////////////////////////////////////////////////////////////////////////
-
-            if !input.get_key(Keycode::LAlt) && (input.get_button_down(1) || input.get_button_down(2)) {
-                history.pop_undos();
-                history.push_back(history.get_current().clone());
-            } else if !input.get_key(Keycode::LAlt) && (input.get_button(1) || input.get_button(2)) {
-                let color = if input.get_button(2) {
-                    vec4(0.0, 0.0, 0.0, 0.0)
-                } else {
-                    color_picked
-                };
-                paint(&compute_shader, history.get_current(), color, cursor_coords, prev_cursor_coords, brush_size);
-            } else if input.get_key(Keycode::LAlt) {
-                /////////////////////////////////////////////////////////////////////////////////////////////////////////////////
-                // This is only here because there is a bug in the input library with the first click, and I am too lazy to properly fix it
-                if !first_click && input.get_button(1) {
-                    prev_cursor_pos = input.get_cursor_pos();
-                    prev_brush_size = brush_size;
-                    first_click = true;
+        } else if !is_hovered && !(toolbox.background.enabled && view.update(&input, &keybindings, screen_coords)) && !toolbox.ruler.update(&input, cursor_coords) {
+            match active_tool {
+                ToolKind::Brush if toolbox.laser.enabled && !toolbox.registry.is_gesture(resizing) => {
+                    if input.get_button_down(1) {
+                        // The laser stays on the screen, it doesn't move with the canvas
+                        toolbox.laser.begin(screen_coords, toolbox.color, toolbox.brush_size);
+                    } else if input.get_button(1) {
+                        toolbox.laser.add_point(screen_coords);
+                    } else {
+                        toolbox.laser.end();
+                    }
                 }
-                /////////////////////////////////////////////////////////////////////////////////////////////////////////////////
-
-                if input.get_button_down(1) {
-                    prev_cursor_pos = input.get_cursor_pos();
-                    prev_brush_size = brush_size;
+                ToolKind::Brush => {
+                    // With the spotlight on, the resize gesture sets the size of its hole instead of the brush
+                    let size = if toolbox.spotlight.enabled && toolbox.registry.is_gesture(resizing) { &mut toolbox.spotlight.size } else { &mut toolbox.brush_size };
+                    let mut guides = StrokeGuides { ruler: &mut toolbox.ruler, grid: &toolbox.grid };
+                    let mut events = Vec::new();
+                    let mut context = ToolContext {
+                        history: &mut *history,
+                        compute_shader: &compute_shader,
+                        guides: &mut guides,
+                        symmetry: &toolbox.symmetry,
+                        recognizer: &mut toolbox.recognizer,
+                        color: &mut toolbox.color,
+                        brush_size: size,
+                        cursor: cursor_coords,
+                        prev_cursor: prev_cursor_coords,
+                        screen_cursor: input.get_cursor_pos(),
+                        straight_line: keybindings.held(Action::StraightLine, &input),
+                        events: &mut events
+                    };
+                    toolbox.registry.update(input.get_button(1), input.get_button(2), resizing, &mut context);
+                    toolbox.registry.preview(&mut context, overlay.draw());
+                    if let Some(session) = &mut session {
+                        session.send_stroke(page, toolbox.registry.active_name(), &events, toolbox.registry.is_active());
+                    }
+                    if let Some(anchor) = toolbox.registry.anchor() {
+                        cursor_coords = anchor;
+                    }
+                }
+                ToolKind::RectangleSelect | ToolKind::LassoSelect => {
+                    selection.update(&input, toolbox.kind, cursor_coords, history, &stamp_shader);
+                }
+                ToolKind::Fill => {
+                    if input.get_button_down(1) {
+                        toolbox.bucket.fill(history, cursor_coords, toolbox.color, view.area(window.get_size()));
+                    }
+                }
+                ToolKind::Redact => {
+                    toolbox.redactor.update(&input, cursor_coords, history, &redact_shader);
+                }
+                ToolKind::Gradient => {
+                    toolbox.gradient.update(&input, cursor_coords, history, &gradient_shader, view.area(window.get_size()));
                 }
-                if input.get_button(1) {
-                    brush_size = abs(input.get_cursor_pos().x + 1.0 * prev_brush_size as f32 - prev_cursor_pos.x) as i32;
-                    cursor_coords = vec2(prev_cursor_pos.x, window.get_size().y - prev_cursor_pos.y);
+                ToolKind::Measure => {
+                    // Letting go while holding the straight line key keeps the dimension line on the canvas
+                    if let Some(measurement) = toolbox.measure.update(&input, cursor_coords) {
+                        if keybindings.held(Action::StraightLine, &input) {
+                            toolbox.measure.keep(measurement, history, &compute_shader, toolbox.color, window.get_content_scale());
+                        }
+                    }
                 }
-                if input.get_button_down(2) {
-                    history.get_current().clear();
+                ToolKind::Stamp => {
+                    if keybindings.pressed(Action::ResetCounter, &input) {
+                        toolbox.stamps.reset_counter();
+                    }
+                    if input.get_button_down(1) {
+                        history.begin_step();
+                        toolbox.stamps.place(&compute_shader, history.get_current(), toolbox.grid.snap(cursor_coords), toolbox.color, toolbox.brush_size);
+                    } else if !input.get_button(1) {
+                        toolbox.stamps.draw(&compute_shader, overlay.draw(), toolbox.grid.snap(cursor_coords), toolbox.color, toolbox.brush_size);
+                    }
+                }
+                ToolKind::Callout => {
+                    toolbox.callout.update(&input, cursor_coords, history);
+                }
+                ToolKind::Eyedropper => {
+                    if input.get_button(1) {
+                        if let Some(color) = toolbox.eyedropper.sample(history.get_current(), cursor_coords) {
+                            toolbox.color = color;
+                        }
+                    }
                 }
             }
         }
 
+        toolbox.laser.fade(&compute_shader);
+        if toolbox.kind == ToolKind::Callout {
+            toolbox.callout.preview(overlay.draw());
+        }
+        if let Some(outline) = selection.outline().or_else(|| toolbox.redactor.outline()).or_else(|| toolbox.gradient.outline()).or_else(|| toolbox.measure.outline()) {
+            paint_polyline(&compute_shader, overlay.draw(), vec4(0.3, 0.6, 1.0, 1.0), &outline, 1);
+        }
+        if let Some(session) = &mut session {
+            session.send_cursor(interactive.then_some(cursor_coords), toolbox.color);
+            session.draw_peers(overlay.draw(), &compute_shader, page);
+        }
+
         // Render to screen
+        screen.clear();
+        history.get_current().render(&tile_shader, &screen, view.offset(), view.zoom(), false);
+        overlay.get_tiles().render(&tile_shader, &screen, view.offset(), view.zoom(), true);
         shader.set_ivector2(&cursor_coords, "cursor_pos");
         shader.set_ivector2(&window.get_size(), "screen_size");
-        shader.set_int(brush_size, "brush_size");
+        view.bind(&shader);
+        // No cursor ring while presenting, the real cursor is doing something else
+        shader.set_int(if interactive { toolbox.brush_size } else { 0 }, "brush_size");
+        shader.set_int(3, "laser");
+        shader.set_int((toolbox.laser.enabled && toolbox.kind == ToolKind::Brush && interactive) as i32, "laser_mode");
+        shader.set_vector4(&toolbox.color, "laser_color");
+        toolbox.spotlight.bind(&shader);
+        toolbox.symmetry.bind(&shader);
+        toolbox.grid.bind(&shader);
+        toolbox.background.bind(&shader);
+        toolbox.ruler.bind(&shader);
 
         mesh.bind();
-        history.get_current().bind(gl::TEXTURE0);
+        screen.bind(gl::TEXTURE0);
+        toolbox.laser.get_texture().bind(gl::TEXTURE3);
+        selection.bind(&shader);
         shader.bind();
         draw_indexed(6);
         shader.unbind();
-        history.get_current().unbind(gl::TEXTURE0);
+        selection.unbind();
+        toolbox.laser.get_texture().unbind(gl::TEXTURE3);
+        screen.unbind(gl::TEXTURE0);
         mesh.unbind();
 
         // Gui
-        is_hovered = gui.show_gui(&mut window.p_window, &mut color_picked, &mut first_click, &mut brush_size, &mut history);
+        if interactive {
+            let measurement = toolbox.measure.label(window.get_content_scale());
+            is_hovered = gui.show_gui(&mut window.p_window, &mut pages, &mut toolbox, &keybindings, measurement);
+        } else {
+            is_hovered = false;
+        }
         if input.get_button(2) {
             is_hovered = false;
         }
 
         window.swap_buffers();
         clear(0.0, 0.0, 0.0, 0.0);
+
+        if last_save.elapsed().as_secs_f32() > SETTINGS_SAVE_INTERVAL {
+            last_save = time::Instant::now();
+            let settings = Settings::capture(&toolbox, &pages);
+            if settings != saved_settings {
+                if let Err(error) = settings.save() {
+                    eprintln!("[ERROR::SETTINGS::SAVE] {}", error);
+                }
+                saved_settings = settings;
+            }
+        }
+
+        if let Some(path) = &autosave {
+            let idle = !input.get_button(1) && !input.get_button(2) && !input.get_button(3);
+            if idle && last_autosave.elapsed().as_secs_f32() > AUTOSAVE_INTERVAL && pages.revision() != saved_revision {
+                last_autosave = time::Instant::now();
+                saved_revision = pages.revision();
+                if let Err(error) = pages.to_project().save(path) {
+                    eprintln!("[ERROR::PROJECT::AUTOSAVE] {}: {}", path.display(), error);
+                }
+            }
+        }
+    }
+
+    if let Some(path) = &autosave {
+        if pages.revision() != saved_revision {
+            if let Err(error) = pages.to_project().save(path) {
+                eprintln!("[ERROR::PROJECT::AUTOSAVE] {}: {}", path.display(), error);
+            }
+        }
     }
-}
 
-fn paint(compute_shader: &ComputeShader, texture: &Texture2D, color: Vec4, cursor_coords: Vec2, prev_cursor_coords: Vec2, brush_size: i32) {
-    compute_shader.set_ivector2(&cursor_coords, "cursor_pos");
-    compute_shader.set_ivector2(&prev_cursor_coords, "prev_cursor_pos");
-    compute_shader.set_int(brush_size, "brush_size");
-    compute_shader.set_vector4(&color, "color");
-
-    let size = clamp(abs(cursor_coords - prev_cursor_coords) + 2.0 * brush_size as f32, vec2(0.0, 0.0), vec2(texture.get_width() as f32, texture.get_height() as f32));
-    texture.bind_image(gl::TEXTURE0);
-    compute_shader.bind();
-    compute_shader.dispatch(ceil(size.x / 10.0) as u32, ceil(size.y / 10.0) as u32, 1);
-    compute_shader.wait();
-    compute_shader.unbind();
-    texture.unbind_image(gl::TEXTURE0);
-}
\ No newline at end of file
+    let settings = Settings::capture(&toolbox, &pages);
+    if settings != saved_settings {
+        if let Err(error) = settings.save() {
+            eprintln!("[ERROR::SETTINGS::SAVE] {}", error);
+        }
+    }
+}
@@ -0,0 +1,71 @@
+use glm::*;
+use crate::brush::*;
+use crate::history::History;
+use crate::input::Input;
+use crate::open_gl::shaders::ComputeShader;
+
+const TICK_SIZE: f32 = 8.0;
+
+pub struct Measure {
+    start: Option<Vec2>,
+    end: Vec2
+}
+
+impl Measure {
+    pub fn new() -> Self {
+        Measure { start: None, end: vec2(0.0, 0.0) }
+    }
+
+    // Returns the start and end of a measurement when the button is let go
+    pub fn update(&mut self, input: &Input, cursor: Vec2) -> Option<(Vec2, Vec2)> {
+        if input.get_button_down(1) {
+            self.start = Some(cursor);
+        }
+        self.end = cursor;
+        if input.get_button(1) {
+            return None;
+        }
+        self.start.take().filter(|start| *start != cursor).map(|start| (start, cursor))
+    }
+
+    // Puts the dimension line with its length on the canvas
+    pub fn keep(&self, (start, end): (Vec2, Vec2), history: &mut History, compute_shader: &ComputeShader, color: Vec4, content_scale: Vec2) {
+        history.begin_step();
+        paint_polyline(compute_shader, history.get_current(), color, &dimension_line(start, end), 1);
+
+        let text = distance_text(start, end, content_scale);
+        let (width, _) = crate::font::measure(&text, 2);
+        let dir = normalize(end - start);
+        let normal = vec2(-dir.y, dir.x);
+        let pos = (start + end) * 0.5 + normal * TICK_SIZE - vec2(width as f32 * 0.5, 0.0);
+        draw_text(history.get_current(), &text, pos, 2, color);
+    }
+
+    pub fn outline(&self) -> Option<Vec<Vec2>> {
+        self.start.filter(|start| *start != self.end).map(|start| dimension_line(start, self.end))
+    }
+
+    // Live readout for the measurement that is being dragged out
+    pub fn label(&self, content_scale: Vec2) -> Option<String> {
+        let start = self.start?;
+        let d = self.end - start;
+        let physical = vec2(d.x * content_scale.x, d.y * content_scale.y);
+        let mut label = format!("{}\ndx {:.0}  dy {:.0}\n{:.1} deg", distance_text(start, self.end, content_scale), physical.x, physical.y, d.y.atan2(d.x).to_degrees());
+        if content_scale != vec2(1.0, 1.0) {
+            label += &format!("\n{:.1} pt at {:.2}x scale", length(d), content_scale.x);
+        }
+        Some(label)
+    }
+}
+
+fn distance_text(start: Vec2, end: Vec2, content_scale: Vec2) -> String {
+    let d = end - start;
+    format!("{:.1} px", length(vec2(d.x * content_scale.x, d.y * content_scale.y)))
+}
+
+// The line with a tick across each end
+fn dimension_line(start: Vec2, end: Vec2) -> Vec<Vec2> {
+    let dir = normalize(end - start);
+    let tick = vec2(-dir.y, dir.x) * TICK_SIZE * 0.5;
+    vec![start + tick, start - tick, start, end, end + tick, end - tick]
+}
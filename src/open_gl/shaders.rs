@@ -102,6 +102,15 @@ impl StandardShader {
             gl::UseProgram(0);
         }
     }
+    pub fn set_float(&self, value: f32, variable: &str) {
+        unsafe {
+            gl::UseProgram(self.program_id);
+            let c_variable = CString::new(variable).unwrap();
+            let location = gl::GetUniformLocation(self.program_id, c_variable.as_ptr() as *const GLchar);
+            gl::Uniform1f(location, value);
+            gl::UseProgram(0);
+        }
+    }
     pub fn set_vector2(&self, value: &glm::Vec2, variable: &str) {
         unsafe {
             gl::UseProgram(self.program_id);
@@ -111,6 +120,15 @@ impl StandardShader {
             gl::UseProgram(0);
         }
     }
+    pub fn set_vector4(&self, value: &glm::Vec4, variable: &str) {
+        unsafe {
+            gl::UseProgram(self.program_id);
+            let c_string = CString::new(variable).unwrap();
+            let location = gl::GetUniformLocation(self.program_id, c_string.as_ptr() as *const GLchar);
+            gl::Uniform4f(location, value.x, value.y, value.z, value.w);
+            gl::UseProgram(0);
+        }
+    }
     pub fn set_ivector2(&self, value: &glm::Vec2, variable: &str) {
         unsafe {
             gl::UseProgram(self.program_id);
@@ -193,6 +211,15 @@ impl ComputeShader {
             gl::UseProgram(0);
         }
     }
+    pub fn set_float(&self, value: f32, variable: &str) {
+        unsafe {
+            gl::UseProgram(self.program_id);
+            let c_variable = CString::new(variable).unwrap();
+            let location = gl::GetUniformLocation(self.program_id, c_variable.as_ptr() as *const GLchar);
+            gl::Uniform1f(location, value);
+            gl::UseProgram(0);
+        }
+    }
     pub fn set_vector2(&self, value: &glm::Vec2, variable: &str) {
         unsafe {
             gl::UseProgram(self.program_id);
@@ -202,6 +229,25 @@ impl ComputeShader {
             gl::UseProgram(0);
         }
     }
+    pub fn set_float_array(&self, values: &[f32], variable: &str) {
+        unsafe {
+            gl::UseProgram(self.program_id);
+            let c_variable = CString::new(variable).unwrap();
+            let location = gl::GetUniformLocation(self.program_id, c_variable.as_ptr() as *const GLchar);
+            gl::Uniform1fv(location, values.len() as GLsizei, values.as_ptr());
+            gl::UseProgram(0);
+        }
+    }
+    pub fn set_vector4_array(&self, values: &[glm::Vec4], variable: &str) {
+        unsafe {
+            gl::UseProgram(self.program_id);
+            let c_variable = CString::new(variable).unwrap();
+            let location = gl::GetUniformLocation(self.program_id, c_variable.as_ptr() as *const GLchar);
+            let flat: Vec<f32> = values.iter().flat_map(|value| [value.x, value.y, value.z, value.w]).collect();
+            gl::Uniform4fv(location, values.len() as GLsizei, flat.as_ptr());
+            gl::UseProgram(0);
+        }
+    }
     pub fn set_vector4(&self, value: &glm::Vec4, variable: &str) {
         unsafe {
             gl::UseProgram(self.program_id);
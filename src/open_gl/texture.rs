@@ -26,6 +26,13 @@ impl Texture2D {
         }
     }
 
+    // pixels are tightly packed RGBA floats, starting at the bottom left
+    pub fn from_pixels(width: u32, height: u32, pixels: &[f32]) -> Self {
+        let texture = Texture2D::new(width, height);
+        texture.write_pixels(0, 0, width, height, pixels);
+        texture
+    }
+
     // use for sampler2D
     pub fn bind(&self, unit: GLenum) {
         unsafe {
@@ -64,12 +71,37 @@ impl Texture2D {
         }
     }
 
+    pub fn copy_from(&self, source: &Texture2D) {
+        unsafe {
+            gl::CopyImageSubData(source.texture_id, gl::TEXTURE_2D, 0, 0, 0, 0, self.texture_id, gl::TEXTURE_2D, 0, 0, 0, 0,
+                                 self.width.min(source.width) as GLsizei, self.height.min(source.height) as GLsizei, 1);
+        }
+    }
+
+    // Copies a rectangle of the source to the position, whatever falls outside of either texture is skipped
     pub fn clear(&self) {
         unsafe {
             gl::ClearTexImage(self.texture_id, 0, gl::RGBA, gl::FLOAT, std::ptr::null());
         }
     }
 
+    pub fn read_pixels(&self, x: u32, y: u32, width: u32, height: u32) -> Vec<f32> {
+        let mut pixels = vec![0.0; (width * height * 4) as usize];
+        unsafe {
+            gl::GetTextureSubImage(self.texture_id, 0, x as GLint, y as GLint, 0, width as GLsizei, height as GLsizei, 1, gl::RGBA, gl::FLOAT,
+                                   (pixels.len() * std::mem::size_of::<f32>()) as GLsizei, pixels.as_mut_ptr() as *mut std::ffi::c_void);
+        }
+        pixels
+    }
+
+    pub fn write_pixels(&self, x: u32, y: u32, width: u32, height: u32, pixels: &[f32]) {
+        // GL reads width * height RGBA floats from the pointer whatever the slice holds
+        assert!(pixels.len() >= width as usize * height as usize * 4, "write_pixels got {} floats for {}x{} pixels", pixels.len(), width, height);
+        unsafe {
+            gl::TextureSubImage2D(self.texture_id, 0, x as GLint, y as GLint, width as GLsizei, height as GLsizei, gl::RGBA, gl::FLOAT, pixels.as_ptr() as *const std::ffi::c_void);
+        }
+    }
+
     pub fn get_width(&self) -> u32 {
         self.width
     }
@@ -0,0 +1,29 @@
+use crate::tiles::{Surface, Tiles};
+
+// Layer for tool previews that is drawn on top of the canvas but never ends up in the history
+pub struct Overlay {
+    tiles: Tiles,
+    dirty: bool
+}
+
+impl Overlay {
+    pub fn new() -> Self {
+        Overlay { tiles: Tiles::new(), dirty: false }
+    }
+
+    pub fn clear(&mut self) {
+        if self.dirty {
+            self.tiles.clear();
+            self.dirty = false;
+        }
+    }
+
+    pub fn draw(&mut self) -> &Tiles {
+        self.dirty = true;
+        &self.tiles
+    }
+
+    pub fn get_tiles(&self) -> &Tiles {
+        &self.tiles
+    }
+}
@@ -0,0 +1,180 @@
+use std::fs;
+use std::time::{SystemTime, UNIX_EPOCH};
+use glm::vec2;
+use crate::history::History;
+use crate::image::save_png;
+use crate::paths::export_dir;
+use crate::project::Project;
+use crate::tiles::{Surface, Tiles};
+
+pub const MAX_SHORTCUT_PAGES: usize = 9;
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum PageAction {
+    Select(usize),
+    Add,
+    Duplicate,
+    Delete,
+    ExportAll
+}
+
+// Independent canvases, each with its own undo history. They have no edges, but exports and saves
+// always cover at least the screen they started on
+pub struct Pages {
+    pages: Vec<History>,
+    current: usize,
+    width: u32,
+    height: u32,
+    request: Option<PageAction>,
+    // Bumped whenever pages are added, removed, reordered or swapped out
+    changes: u64,
+    pub status: Option<String>
+}
+
+impl Pages {
+    pub fn new(width: u32, height: u32) -> Self {
+        let mut pages = Pages { pages: Vec::new(), current: 0, width, height, request: None, changes: 0, status: None };
+        pages.pages.push(pages.blank());
+        pages
+    }
+
+    pub fn current(&self) -> &History {
+        &self.pages[self.current]
+    }
+
+    pub fn current_mut(&mut self) -> &mut History {
+        &mut self.pages[self.current]
+    }
+
+    pub fn get_mut(&mut self, index: usize) -> Option<&mut History> {
+        self.pages.get_mut(index)
+    }
+
+    pub fn current_index(&self) -> usize {
+        self.current
+    }
+
+    pub fn len(&self) -> usize {
+        self.pages.len()
+    }
+
+    // Differs from any earlier value once something on any page changed
+    pub fn revision(&self) -> (u64, u64) {
+        (self.changes, self.pages.iter().map(|page| page.get_edits()).sum())
+    }
+
+    pub fn set_max_undos(&mut self, max_undos: i32) {
+        for page in &mut self.pages {
+            page.max_undos = max_undos;
+        }
+    }
+
+    // Actions are carried out at the start of the next frame, once whatever is still floating has been put down
+    pub fn request(&mut self, action: PageAction) {
+        self.request = Some(action);
+    }
+
+    pub fn take_request(&mut self) -> Option<PageAction> {
+        self.request.take()
+    }
+
+    pub fn apply(&mut self, action: PageAction) {
+        self.changes += 1;
+        match action {
+            PageAction::Select(index) => {
+                if index < self.pages.len() {
+                    self.current = index;
+                }
+            }
+            PageAction::Add => {
+                let page = self.blank();
+                self.insert(page);
+            }
+            PageAction::Duplicate => {
+                let mut page = History::new(self.current().get_current().duplicate());
+                page.max_undos = self.current().max_undos;
+                self.insert(page);
+            }
+            PageAction::Delete => {
+                if self.pages.len() > 1 {
+                    self.pages.remove(self.current);
+                    self.current = self.current.min(self.pages.len() - 1);
+                } else {
+                    self.pages[0] = self.blank();
+                }
+            }
+            PageAction::ExportAll => {
+                self.status = Some(match self.export_all() {
+                    Ok(count) => format!("Exported {} pages to {}", count, export_dir().display()),
+                    Err(error) => format!("Export failed: {}", error)
+                });
+            }
+        }
+    }
+
+    // The current page's pixels, the screen and everything drawn around it
+    pub fn image(&self) -> (u32, u32, Vec<f32>) {
+        let (x, y, width, height) = self.area([self.current()]);
+        (width, height, self.current().get_current().read(x, y, width, height))
+    }
+
+    // One PNG per page, numbered in order and sharing a timestamp
+    fn export_all(&self) -> Result<usize, String> {
+        let dir = export_dir();
+        fs::create_dir_all(&dir).map_err(|error| error.to_string())?;
+        let timestamp = SystemTime::now().duration_since(UNIX_EPOCH).map_or(0, |time| time.as_secs());
+
+        for (i, page) in self.pages.iter().enumerate() {
+            let (x, y, width, height) = self.area([page]);
+            let path = dir.join(format!("overdraw-{}-page-{}.png", timestamp, i + 1));
+            save_png(&path, width, height, &page.get_current().read(x, y, width, height))?;
+        }
+        Ok(self.pages.len())
+    }
+
+    // Every page over the same area, so they line up again when loaded
+    pub fn to_project(&self) -> Project {
+        let (x, y, width, height) = self.area(&self.pages);
+        let pages = self.pages.iter().map(|page| page.get_current().read(x, y, width, height)).collect();
+        Project { width, height, origin: Some((x, y)), current: self.current, pages }
+    }
+
+    // Replaces every page
+    pub fn load(&mut self, project: &Project) {
+        let (x, y) = project.origin.unwrap_or((0, self.height as i32 - project.height as i32));
+        let max_undos = self.current().max_undos;
+        self.pages = project.pages.iter().map(|pixels| {
+            let tiles = Tiles::new();
+            tiles.write(x, y, project.width, project.height, pixels);
+            let mut history = History::new(tiles);
+            history.max_undos = max_undos;
+            history
+        }).collect();
+        self.current = project.current.min(self.pages.len() - 1);
+        self.changes += 1;
+    }
+
+    // The screen and whatever was drawn around it on the pages, as bottom left texel and size
+    fn area<'a>(&self, pages: impl IntoIterator<Item = &'a History>) -> (i32, i32, u32, u32) {
+        let (mut min_corner, mut max_corner) = (vec2(0.0, 0.0), vec2(self.width as f32, self.height as f32));
+        for (page_min, page_max) in pages.into_iter().filter_map(|page| page.get_current().bounds()) {
+            min_corner = glm::min(min_corner, page_min);
+            max_corner = glm::max(max_corner, page_max);
+        }
+        (min_corner.x as i32, min_corner.y as i32, (max_corner.x - min_corner.x) as u32, (max_corner.y - min_corner.y) as u32)
+    }
+
+    // New pages go right after the current one and become current
+    fn insert(&mut self, page: History) {
+        self.current += 1;
+        self.pages.insert(self.current, page);
+    }
+
+    fn blank(&self) -> History {
+        let mut history = History::new(Tiles::new());
+        if let Some(page) = self.pages.first() {
+            history.max_undos = page.max_undos;
+        }
+        history
+    }
+}
@@ -0,0 +1,112 @@
+// Makes the window ignore the mouse so clicks land on whatever is below it, this version of GLFW has no hint for it
+use imgui_glfw_rs::glfw::{Glfw, Window};
+
+#[cfg(target_os = "linux")]
+mod x11 {
+    use std::os::raw::{c_int, c_ulong, c_void};
+    use std::sync::OnceLock;
+    use libloading::Library;
+
+    // ShapeInput from the X Shape extension, None resets the shape to the whole window
+    pub const SHAPE_INPUT: c_int = 2;
+    pub const NONE: c_ulong = 0;
+
+    // libXfixes is loaded the first time it's needed, without it only click-through presenting is missing
+    pub struct Xfixes {
+        pub create_region: unsafe extern "C" fn(display: *mut c_void, rectangles: *mut c_void, count: c_int) -> c_ulong,
+        pub destroy_region: unsafe extern "C" fn(display: *mut c_void, region: c_ulong),
+        pub set_window_shape_region: unsafe extern "C" fn(display: *mut c_void, window: c_ulong, shape_kind: c_int, x_offset: c_int, y_offset: c_int, region: c_ulong),
+        _library: Library
+    }
+
+    pub fn xfixes() -> Result<&'static Xfixes, String> {
+        static XFIXES: OnceLock<Result<Xfixes, String>> = OnceLock::new();
+        XFIXES.get_or_init(|| unsafe { load() }).as_ref().map_err(|error| format!("click-through needs libXfixes: {}", error))
+    }
+
+    unsafe fn load() -> Result<Xfixes, String> {
+        let library = Library::new("libXfixes.so.3").or_else(|_| Library::new("libXfixes.so")).map_err(|error| error.to_string())?;
+        let create_region = *library.get(b"XFixesCreateRegion\0").map_err(|error| error.to_string())?;
+        let destroy_region = *library.get(b"XFixesDestroyRegion\0").map_err(|error| error.to_string())?;
+        let set_window_shape_region = *library.get(b"XFixesSetWindowShapeRegion\0").map_err(|error| error.to_string())?;
+        Ok(Xfixes { create_region, destroy_region, set_window_shape_region, _library: library })
+    }
+
+    #[link(name = "X11")]
+    extern "C" {
+        pub fn XFlush(display: *mut c_void) -> c_int;
+    }
+}
+
+#[cfg(target_os = "linux")]
+pub fn set_mouse_passthrough(glfw: &Glfw, window: &Window, enabled: bool) -> Result<(), String> {
+    let display = glfw.get_x11_display();
+    if display.is_null() {
+        return Err("not running on X11".to_string());
+    }
+    let window = window.get_x11_window() as std::os::raw::c_ulong;
+    // Without libXfixes the input shape was never changed, so there's nothing to reset
+    let xfixes = match x11::xfixes() {
+        Ok(xfixes) => xfixes,
+        Err(_) if !enabled => return Ok(()),
+        Err(error) => return Err(error)
+    };
+
+    // An empty input shape lets every click through while the window stays visible
+    unsafe {
+        if enabled {
+            let region = (xfixes.create_region)(display, std::ptr::null_mut(), 0);
+            (xfixes.set_window_shape_region)(display, window, x11::SHAPE_INPUT, 0, 0, region);
+            (xfixes.destroy_region)(display, region);
+        } else {
+            (xfixes.set_window_shape_region)(display, window, x11::SHAPE_INPUT, 0, 0, x11::NONE);
+        }
+        x11::XFlush(display);
+    }
+    Ok(())
+}
+
+#[cfg(target_os = "windows")]
+mod win32 {
+    use std::os::raw::c_void;
+
+    pub const GWL_EXSTYLE: i32 = -20;
+    pub const WS_EX_TRANSPARENT: isize = 0x20;
+    pub const WS_EX_LAYERED: isize = 0x80000;
+    pub const LWA_ALPHA: u32 = 0x2;
+
+    #[link(name = "user32")]
+    extern "system" {
+        pub fn GetWindowLongPtrW(window: *mut c_void, index: i32) -> isize;
+        pub fn SetWindowLongPtrW(window: *mut c_void, index: i32, value: isize) -> isize;
+        pub fn SetLayeredWindowAttributes(window: *mut c_void, key: u32, alpha: u8, flags: u32) -> i32;
+    }
+}
+
+#[cfg(target_os = "windows")]
+pub fn set_mouse_passthrough(_glfw: &Glfw, window: &Window, enabled: bool) -> Result<(), String> {
+    let window = window.get_win32_window();
+    if window.is_null() {
+        return Err("no native window".to_string());
+    }
+
+    // Transparent windows only let clicks through when they're layered as well
+    unsafe {
+        let style = win32::GetWindowLongPtrW(window, win32::GWL_EXSTYLE);
+        if enabled {
+            win32::SetWindowLongPtrW(window, win32::GWL_EXSTYLE, style | win32::WS_EX_LAYERED | win32::WS_EX_TRANSPARENT);
+            win32::SetLayeredWindowAttributes(window, 0, 255, win32::LWA_ALPHA);
+        } else {
+            win32::SetWindowLongPtrW(window, win32::GWL_EXSTYLE, style & !(win32::WS_EX_LAYERED | win32::WS_EX_TRANSPARENT));
+        }
+    }
+    Ok(())
+}
+
+#[cfg(not(any(target_os = "linux", target_os = "windows")))]
+pub fn set_mouse_passthrough(_glfw: &Glfw, _window: &Window, enabled: bool) -> Result<(), String> {
+    if enabled {
+        return Err("not supported on this platform".to_string());
+    }
+    Ok(())
+}
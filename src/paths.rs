@@ -0,0 +1,58 @@
+use std::env;
+use std::path::PathBuf;
+use std::sync::OnceLock;
+
+static CONFIG_OVERRIDE: OnceLock<PathBuf> = OnceLock::new();
+
+// Replaces the platform's config directory for the rest of the run, only the first call counts
+pub fn set_config_dir(dir: PathBuf) {
+    let _ = CONFIG_OVERRIDE.set(dir);
+}
+
+// Where user files like stamps live, following the platform's convention
+pub fn config_dir() -> PathBuf {
+    if let Some(dir) = CONFIG_OVERRIDE.get() {
+        return dir.clone();
+    }
+    let base = if cfg!(windows) {
+        env::var_os("APPDATA").map(PathBuf::from)
+    } else if cfg!(target_os = "macos") {
+        env::var_os("HOME").map(|home| PathBuf::from(home).join("Library/Application Support"))
+    } else {
+        env::var_os("XDG_CONFIG_HOME").filter(|dir| !dir.is_empty()).map(PathBuf::from)
+            .or_else(|| env::var_os("HOME").map(|home| PathBuf::from(home).join(".config")))
+    };
+    base.unwrap_or_else(|| PathBuf::from(".")).join("overdraw")
+}
+
+// Where the control socket lives, a directory only the user can get into. The temporary directory is shared
+// without XDG_RUNTIME_DIR, so the directory is checked instead of trusted, someone else may have made it first
+#[cfg(unix)]
+pub fn runtime_dir() -> Result<PathBuf, String> {
+    use std::fs;
+    use std::io::ErrorKind;
+    use std::os::unix::fs::{DirBuilderExt, MetadataExt};
+    let base = env::var_os("XDG_RUNTIME_DIR").filter(|dir| !dir.is_empty()).map(PathBuf::from).unwrap_or_else(env::temp_dir);
+    // SAFETY: getuid can't fail and touches no memory
+    let uid = unsafe { libc::getuid() };
+    let dir = base.join(format!("overdraw-{}", uid));
+    match fs::DirBuilder::new().mode(0o700).create(&dir) {
+        Err(error) if error.kind() != ErrorKind::AlreadyExists => return Err(format!("{}: {}", dir.display(), error)),
+        _ => {}
+    }
+
+    let metadata = fs::symlink_metadata(&dir).map_err(|error| format!("{}: {}", dir.display(), error))?;
+    if !metadata.is_dir() || metadata.uid() != uid || metadata.mode() & 0o077 != 0 {
+        return Err(format!("{} has to be a directory that only user {} can access", dir.display(), uid));
+    }
+    Ok(dir)
+}
+
+// Where exported images go, the pictures folder if there is one
+pub fn export_dir() -> PathBuf {
+    let pictures = env::var_os(if cfg!(windows) { "USERPROFILE" } else { "HOME" }).map(|home| PathBuf::from(home).join("Pictures"));
+    match pictures {
+        Some(pictures) if pictures.is_dir() => pictures.join("overdraw"),
+        _ => PathBuf::from(".")
+    }
+}
@@ -0,0 +1,93 @@
+use std::fs;
+use std::io::Cursor;
+use std::path::Path;
+use crate::image::{decode_png, encode_png};
+
+// Every page as a PNG behind a small header, all numbers little endian:
+// magic, format version, width, height, origin x and y, current page, page count, then each page's byte length and PNG data
+const MAGIC: &[u8; 8] = b"OVERDRAW";
+const FORMAT_VERSION: u32 = 1;
+const PNG_SIGNATURE: &[u8; 8] = b"\x89PNG\r\n\x1a\n";
+pub const EXTENSION: &str = "overdraw";
+
+// Pixels of every page in the layout the canvas uses, tightly packed RGBA with the bottom row first
+pub struct Project {
+    pub width: u32,
+    pub height: u32,
+    // The canvas texel the bottom left pixel goes to, None puts the top left corner at the top left of the screen
+    pub origin: Option<(i32, i32)>,
+    pub current: usize,
+    pub pages: Vec<Vec<f32>>
+}
+
+impl Project {
+    // Tells projects and plain PNGs apart by their first bytes, not the extension
+    pub fn load(path: &Path) -> Result<Self, String> {
+        let data = fs::read(path).map_err(|error| error.to_string())?;
+        if data.starts_with(MAGIC) {
+            Project::decode(&data)
+        } else if data.starts_with(PNG_SIGNATURE) {
+            let (width, height, pixels) = decode_png(Cursor::new(data))?;
+            Ok(Project { width, height, origin: None, current: 0, pages: vec![pixels] })
+        } else {
+            Err("not a PNG or overdraw project".to_string())
+        }
+    }
+
+    // Written next to the real file first, so a crash halfway leaves the previous save intact
+    pub fn save(&self, path: &Path) -> Result<(), String> {
+        let (x, y) = self.origin.unwrap_or((0, 0));
+        let mut data = MAGIC.to_vec();
+        for value in [FORMAT_VERSION, self.width, self.height, x as u32, y as u32, self.current as u32, self.pages.len() as u32] {
+            data.extend(value.to_le_bytes());
+        }
+        for pixels in &self.pages {
+            let mut png = Vec::new();
+            encode_png(&mut png, self.width, self.height, pixels)?;
+            data.extend((png.len() as u32).to_le_bytes());
+            data.extend(png);
+        }
+
+        let temporary = path.with_extension(format!("{}.tmp", EXTENSION));
+        fs::write(&temporary, data).map_err(|error| error.to_string())?;
+        fs::rename(&temporary, path).map_err(|error| error.to_string())
+    }
+
+    fn decode(data: &[u8]) -> Result<Self, String> {
+        let mut rest = &data[MAGIC.len()..];
+        let version = next(&mut rest)?;
+        if version != FORMAT_VERSION {
+            return Err(format!("unsupported project version {}, expected {}", version, FORMAT_VERSION));
+        }
+        let (width, height) = (next(&mut rest)?, next(&mut rest)?);
+        let origin = (next(&mut rest)? as i32, next(&mut rest)? as i32);
+        let current = next(&mut rest)? as usize;
+        let count = next(&mut rest)? as usize;
+
+        let mut pages = Vec::new();
+        for i in 0..count {
+            let length = next(&mut rest)? as usize;
+            if rest.len() < length {
+                return Err("file is truncated".to_string());
+            }
+            let (png, tail) = rest.split_at(length);
+            rest = tail;
+            let (page_width, page_height, pixels) = decode_png(png)?;
+            if (page_width, page_height) != (width, height) {
+                return Err(format!("page {} is {}x{}, expected {}x{}", i + 1, page_width, page_height, width, height));
+            }
+            pages.push(pixels);
+        }
+        if pages.is_empty() {
+            return Err("project has no pages".to_string());
+        }
+
+        Ok(Project { width, height, origin: Some(origin), current: current.min(pages.len() - 1), pages })
+    }
+}
+
+fn next(rest: &mut &[u8]) -> Result<u32, String> {
+    let (value, tail) = rest.split_first_chunk::<4>().ok_or("file is truncated")?;
+    *rest = tail;
+    Ok(u32::from_le_bytes(*value))
+}
@@ -0,0 +1,76 @@
+use glm::*;
+use serde::{Deserialize, Serialize};
+use crate::history::History;
+use crate::input::Input;
+use crate::open_gl::shaders::ComputeShader;
+use crate::open_gl::texture::Texture2D;
+use crate::tiles::Surface;
+
+// Keep in sync with the modes in REDACT_SHADER
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum RedactMode {
+    Pixelate,
+    Blur,
+    BlackBox
+}
+
+pub struct Redactor {
+    pub mode: RedactMode,
+    pub strength: i32,
+    start: Option<Vec2>,
+    end: Vec2
+}
+
+impl Redactor {
+    pub fn new() -> Self {
+        Redactor { mode: RedactMode::Pixelate, strength: 12, start: None, end: vec2(0.0, 0.0) }
+    }
+
+    pub fn update(&mut self, input: &Input, cursor: Vec2, history: &mut History, redact_shader: &ComputeShader) {
+        if input.get_button_down(1) {
+            self.start = Some(cursor);
+        }
+        self.end = cursor;
+        if !input.get_button(1) {
+            if let Some(start) = self.start.take() {
+                self.apply(history, start, cursor, redact_shader);
+            }
+        }
+    }
+
+    // Rectangle that is currently being dragged out
+    pub fn outline(&self) -> Option<Vec<Vec2>> {
+        let a = self.start?;
+        let b = self.end;
+        Some(vec![a, vec2(b.x, a.y), b, vec2(a.x, b.y), a])
+    }
+
+    fn apply(&self, history: &mut History, a: Vec2, b: Vec2, redact_shader: &ComputeShader) {
+        let min_corner = floor(min(a, b));
+        let max_corner = ceil(max(a, b));
+        let size = max_corner - min_corner;
+        if size.x < 1.0 || size.y < 1.0 {
+            return;
+        }
+
+        // Blurring reaches past the rectangle, so the source gets a margin
+        let strength = Ord::max(self.strength, 1);
+        let source_min = min_corner - vec2(strength as f32, strength as f32);
+        let source_size = size + vec2(2.0 * strength as f32, 2.0 * strength as f32);
+        let pixels = history.get_current().read(source_min.x as i32, source_min.y as i32, source_size.x as u32, source_size.y as u32);
+        let source = Texture2D::from_pixels(source_size.x as u32, source_size.y as u32, &pixels);
+        history.begin_step();
+
+        redact_shader.set_ivector2(&min_corner, "rect_min");
+        redact_shader.set_ivector2(&size, "rect_size");
+        redact_shader.set_ivector2(&source_min, "source_origin");
+        redact_shader.set_int(self.mode as i32, "mode");
+        redact_shader.set_int(strength, "strength");
+        redact_shader.set_int(1, "source");
+
+        source.bind(gl::TEXTURE1);
+        history.get_current().regions(min_corner, max_corner, true, &mut |region| region.dispatch(redact_shader));
+        source.unbind(gl::TEXTURE1);
+    }
+}
@@ -0,0 +1,282 @@
+use device_query::Keycode;
+use glm::*;
+use crate::history::History;
+use crate::input::Input;
+use crate::open_gl::shaders::{ComputeShader, StandardShader};
+use crate::open_gl::texture::Texture2D;
+use crate::tiles::Surface;
+use crate::tool::ToolKind;
+
+// Keep in sync with floating_selection() in PIXEL_SHADER
+const HANDLE_SIZE: f32 = 5.0;
+const ROTATE_HANDLE_OFFSET: f32 = 20.0;
+
+enum Drag {
+    Path,
+    Move(Vec2),
+    Scale,
+    Rotate(f32)
+}
+
+struct Floating {
+    texture: Texture2D,
+    center: Vec2,
+    size: Vec2,
+    scale: Vec2,
+    angle: f32,
+    lifted_center: Vec2
+}
+
+impl Floating {
+    fn to_local(&self, p: Vec2) -> Vec2 {
+        rotate(p - self.center, -self.angle)
+    }
+
+    fn half_size(&self) -> Vec2 {
+        vec2(self.size.x * self.scale.x, self.size.y * self.scale.y) * 0.5
+    }
+
+    fn corners(&self) -> [Vec2; 4] {
+        let half = self.half_size();
+        [vec2(-half.x, -half.y), vec2(half.x, -half.y), vec2(half.x, half.y), vec2(-half.x, half.y)]
+            .map(|corner| self.center + rotate(corner, self.angle))
+    }
+}
+
+pub struct Selection {
+    path: Vec<Vec2>,
+    floating: Option<Floating>,
+    drag: Option<Drag>
+}
+
+impl Selection {
+    pub fn new() -> Self {
+        Selection { path: Vec::new(), floating: None, drag: None }
+    }
+
+    pub fn is_floating(&self) -> bool {
+        self.floating.is_some()
+    }
+
+    pub fn update(&mut self, input: &Input, tool: ToolKind, cursor: Vec2, history: &mut History, stamp_shader: &ComputeShader) {
+        if self.floating.is_some() {
+            if input.get_key_down(Keycode::Enter) {
+                self.confirm(history, stamp_shader);
+                return;
+            }
+            if input.get_key_down(Keycode::Escape) {
+                self.cancel(history, stamp_shader);
+                return;
+            }
+            if input.get_key_down(Keycode::Delete) || input.get_key_down(Keycode::Backspace) {
+                self.discard();
+                return;
+            }
+        }
+
+        if input.get_button_down(1) {
+            self.drag = match &self.floating {
+                Some(floating) => Self::hit(floating, cursor),
+                None => None
+            };
+            if self.drag.is_none() {
+                // Clicking next to a floating selection puts it back down
+                self.confirm(history, stamp_shader);
+                self.path = vec![cursor];
+                self.drag = Some(Drag::Path);
+            }
+        } else if input.get_button(1) {
+            match (&self.drag, &mut self.floating) {
+                (Some(Drag::Path), _) => {
+                    if tool == ToolKind::RectangleSelect {
+                        self.path.truncate(1);
+                    }
+                    if self.path.last().map_or(true, |last| *last != cursor) {
+                        self.path.push(cursor);
+                    }
+                }
+                (Some(Drag::Move(grab)), Some(floating)) => floating.center = cursor - *grab,
+                (Some(Drag::Scale), Some(floating)) => {
+                    let local = abs(floating.to_local(cursor)) * 2.0;
+                    floating.scale = vec2(local.x / floating.size.x, local.y / floating.size.y);
+                    if input.get_key(Keycode::LShift) {
+                        let uniform = floating.scale.x.max(floating.scale.y);
+                        floating.scale = vec2(uniform, uniform);
+                    }
+                    floating.scale = max(floating.scale, vec2(0.01, 0.01));
+                }
+                (Some(Drag::Rotate(offset)), Some(floating)) => {
+                    let dir = cursor - floating.center;
+                    floating.angle = dir.y.atan2(dir.x) - *offset;
+                }
+                _ => {}
+            }
+        } else if let Some(drag) = self.drag.take() {
+            if let Drag::Path = drag {
+                self.lift(tool, history);
+                self.path.clear();
+            }
+        }
+    }
+
+    // Closed outline of the selection that is currently being dragged out
+    pub fn outline(&self) -> Option<Vec<Vec2>> {
+        match (&self.drag, self.path.len()) {
+            (Some(Drag::Path), 1) => None,
+            (Some(Drag::Path), 2) => {
+                let (a, b) = (self.path[0], self.path[1]);
+                Some(vec![a, vec2(b.x, a.y), b, vec2(a.x, b.y), a])
+            }
+            (Some(Drag::Path), _) => {
+                let mut outline = self.path.clone();
+                outline.push(self.path[0]);
+                Some(outline)
+            }
+            _ => None
+        }
+    }
+
+    // Stamps the floating selection back onto the canvas
+    pub fn confirm(&mut self, history: &History, stamp_shader: &ComputeShader) {
+        if let Some(floating) = self.floating.take() {
+            let corners = floating.corners();
+            let min_corner = corners.iter().fold(corners[0], |a, b| min(a, *b));
+            let max_corner = corners.iter().fold(corners[0], |a, b| max(a, *b));
+
+            stamp_shader.set_vector2(&floating.center, "center");
+            stamp_shader.set_vector2(&floating.size, "size");
+            stamp_shader.set_vector2(&floating.scale, "scale");
+            stamp_shader.set_float(floating.angle, "angle");
+            stamp_shader.set_int(1, "selection");
+
+            floating.texture.bind(gl::TEXTURE1);
+            history.get_current().regions(min_corner, max_corner, true, &mut |region| region.dispatch(stamp_shader));
+            floating.texture.unbind(gl::TEXTURE1);
+        }
+    }
+
+    // Puts the selection back where it was lifted from
+    pub fn cancel(&mut self, history: &History, stamp_shader: &ComputeShader) {
+        if let Some(floating) = &mut self.floating {
+            floating.center = floating.lifted_center;
+            floating.scale = vec2(1.0, 1.0);
+            floating.angle = 0.0;
+        }
+        self.confirm(history, stamp_shader);
+    }
+
+    // Drops the selection, the hole it was lifted from stays
+    pub fn discard(&mut self) {
+        self.floating = None;
+        self.drag = None;
+    }
+
+    pub fn bind(&self, shader: &StandardShader) {
+        shader.set_int(2, "selection");
+        match &self.floating {
+            Some(floating) => {
+                shader.set_int(1, "floating");
+                shader.set_vector2(&floating.center, "selection_center");
+                shader.set_vector2(&floating.size, "selection_size");
+                shader.set_vector2(&floating.scale, "selection_scale");
+                shader.set_float(floating.angle, "selection_angle");
+                floating.texture.bind(gl::TEXTURE2);
+            }
+            None => shader.set_int(0, "floating")
+        }
+    }
+
+    pub fn unbind(&self) {
+        if let Some(floating) = &self.floating {
+            floating.texture.unbind(gl::TEXTURE2);
+        }
+    }
+
+    fn hit(floating: &Floating, p: Vec2) -> Option<Drag> {
+        let local = floating.to_local(p);
+        let half = floating.half_size();
+
+        if length(local - vec2(0.0, half.y + ROTATE_HANDLE_OFFSET)) < HANDLE_SIZE {
+            let dir = p - floating.center;
+            return Some(Drag::Rotate(dir.y.atan2(dir.x) - floating.angle));
+        }
+        let corner = abs(local) - half;
+        if corner.x.abs() < HANDLE_SIZE && corner.y.abs() < HANDLE_SIZE {
+            return Some(Drag::Scale);
+        }
+        if corner.x <= 0.0 && corner.y <= 0.0 {
+            return Some(Drag::Move(p - floating.center));
+        }
+        None
+    }
+
+    fn contains(&self, tool: ToolKind, p: Vec2) -> bool {
+        if tool == ToolKind::RectangleSelect {
+            return true;
+        }
+
+        // Even-odd rule, the lasso is closed implicitly
+        let mut inside = false;
+        let mut j = self.path.len() - 1;
+        for i in 0..self.path.len() {
+            let (a, b) = (self.path[i], self.path[j]);
+            if (a.y > p.y) != (b.y > p.y) && p.x < (b.x - a.x) * (p.y - a.y) / (b.y - a.y) + a.x {
+                inside = !inside;
+            }
+            j = i;
+        }
+        inside
+    }
+
+    fn lift(&mut self, tool: ToolKind, history: &mut History) {
+        if self.path.len() < 2 {
+            return;
+        }
+
+        let min_corner = floor(self.path.iter().fold(self.path[0], |a, b| min(a, *b)));
+        let max_corner = ceil(self.path.iter().fold(self.path[0], |a, b| max(a, *b)));
+        let (x, y) = (min_corner.x as i32, min_corner.y as i32);
+        let (width, height) = ((max_corner.x - min_corner.x) as u32, (max_corner.y - min_corner.y) as u32);
+        if width == 0 || height == 0 {
+            return;
+        }
+
+        let mut remaining = history.get_current().read(x, y, width, height);
+        let mut lifted = vec![0.0; remaining.len()];
+        let mut empty = true;
+        for py in 0..height {
+            for px in 0..width {
+                let center = min_corner + vec2(px as f32 + 0.5, py as f32 + 0.5);
+                if !self.contains(tool, center) {
+                    continue;
+                }
+                let i = ((py * width + px) * 4) as usize;
+                lifted[i..i + 4].copy_from_slice(&remaining[i..i + 4]);
+                remaining[i..i + 4].fill(0.0);
+                empty &= lifted[i + 3] <= 0.0;
+            }
+        }
+        if empty {
+            return;
+        }
+
+        // Lifting, transforming and stamping back all happen on this one history entry
+        history.begin_step();
+        history.get_current().write(x, y, width, height, &remaining);
+
+        let center = min_corner + vec2(width as f32, height as f32) * 0.5;
+        self.floating = Some(Floating {
+            texture: Texture2D::from_pixels(width, height, &lifted),
+            center,
+            size: vec2(width as f32, height as f32),
+            scale: vec2(1.0, 1.0),
+            angle: 0.0,
+            lifted_center: center
+        });
+    }
+}
+
+fn rotate(v: Vec2, angle: f32) -> Vec2 {
+    let (s, c) = angle.sin_cos();
+    vec2(c * v.x - s * v.y, s * v.x + c * v.y)
+}
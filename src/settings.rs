@@ -0,0 +1,418 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+use glm::Vec4;
+use serde::{Deserialize, Serialize};
+use crate::background::BackgroundPattern;
+use crate::brush::MAX_BRUSH_SIZE;
+use crate::gradient::{GradientMode, GradientStop, MAX_STOPS};
+use crate::pages::Pages;
+use crate::paths::config_dir;
+use crate::redact::RedactMode;
+use crate::spotlight::SpotlightShape;
+use crate::symmetry::SymmetryMode;
+use crate::tool::{ToolKind, Toolbox};
+
+// Bump this when a field changes its meaning, files with another version are set aside and the defaults are used
+pub const SCHEMA_VERSION: u32 = 1;
+
+// Everything is optional, whatever is missing keeps the default the tool starts with
+#[derive(Serialize, Deserialize, Default, Clone, PartialEq, Debug)]
+#[serde(default)]
+pub struct Settings {
+    pub version: u32,
+    pub brush: BrushSettings,
+    pub laser: LaserSettings,
+    pub shape_recognition: RecognizerSettings,
+    pub fill: FillSettings,
+    pub eyedropper: EyedropperSettings,
+    pub redact: RedactSettings,
+    pub gradient: GradientSettings,
+    pub spotlight: SpotlightSettings,
+    pub grid: GridSettings,
+    pub background: BackgroundSettings,
+    pub symmetry: SymmetrySettings,
+    pub callout: CalloutSettings
+}
+
+#[derive(Serialize, Deserialize, Default, Clone, PartialEq, Debug)]
+#[serde(default)]
+pub struct BrushSettings {
+    pub color: Option<[f32; 4]>,
+    pub size: Option<i32>,
+    pub history_size: Option<i32>,
+    pub tool: Option<ToolKind>,
+    // Which of the registered tools the brush slot uses, plugins included
+    pub brush_tool: Option<String>
+}
+
+#[derive(Serialize, Deserialize, Default, Clone, PartialEq, Debug)]
+#[serde(default)]
+pub struct LaserSettings {
+    pub enabled: Option<bool>,
+    pub duration: Option<f32>
+}
+
+#[derive(Serialize, Deserialize, Default, Clone, PartialEq, Debug)]
+#[serde(default)]
+pub struct RecognizerSettings {
+    pub enabled: Option<bool>,
+    pub threshold: Option<f32>,
+    pub hold: Option<f32>
+}
+
+#[derive(Serialize, Deserialize, Default, Clone, PartialEq, Debug)]
+#[serde(default)]
+pub struct FillSettings {
+    pub tolerance: Option<f32>,
+    pub through_edges: Option<bool>
+}
+
+#[derive(Serialize, Deserialize, Default, Clone, PartialEq, Debug)]
+#[serde(default)]
+pub struct EyedropperSettings {
+    pub radius: Option<i32>
+}
+
+#[derive(Serialize, Deserialize, Default, Clone, PartialEq, Debug)]
+#[serde(default)]
+pub struct RedactSettings {
+    pub mode: Option<RedactMode>,
+    pub strength: Option<i32>
+}
+
+#[derive(Serialize, Deserialize, Clone, PartialEq, Debug)]
+pub struct StopSettings {
+    pub position: f32,
+    pub color: [f32; 4]
+}
+
+#[derive(Serialize, Deserialize, Default, Clone, PartialEq, Debug)]
+#[serde(default)]
+pub struct GradientSettings {
+    pub mode: Option<GradientMode>,
+    pub ink_only: Option<bool>,
+    pub stops: Option<Vec<StopSettings>>
+}
+
+#[derive(Serialize, Deserialize, Default, Clone, PartialEq, Debug)]
+#[serde(default)]
+pub struct SpotlightSettings {
+    pub enabled: Option<bool>,
+    pub shape: Option<SpotlightShape>,
+    pub size: Option<i32>,
+    pub aspect: Option<f32>,
+    pub opacity: Option<f32>
+}
+
+#[derive(Serialize, Deserialize, Default, Clone, PartialEq, Debug)]
+#[serde(default)]
+pub struct GridSettings {
+    pub enabled: Option<bool>,
+    pub spacing: Option<i32>,
+    pub color: Option<[f32; 4]>,
+    pub snap: Option<bool>
+}
+
+#[derive(Serialize, Deserialize, Default, Clone, PartialEq, Debug)]
+#[serde(default)]
+pub struct BackgroundSettings {
+    pub enabled: Option<bool>,
+    pub color: Option<[f32; 4]>,
+    pub pattern: Option<BackgroundPattern>,
+    pub spacing: Option<i32>,
+    pub pattern_color: Option<[f32; 4]>
+}
+
+#[derive(Serialize, Deserialize, Default, Clone, PartialEq, Debug)]
+#[serde(default)]
+pub struct SymmetrySettings {
+    pub mode: Option<SymmetryMode>,
+    pub folds: Option<i32>
+}
+
+#[derive(Serialize, Deserialize, Default, Clone, PartialEq, Debug)]
+#[serde(default)]
+pub struct CalloutSettings {
+    pub background: Option<[f32; 4]>,
+    pub border: Option<[f32; 4]>,
+    pub text_color: Option<[f32; 4]>,
+    pub text_scale: Option<i32>
+}
+
+impl Settings {
+    pub fn path() -> PathBuf {
+        config_dir().join("settings.toml")
+    }
+
+    pub fn load() -> Self {
+        Settings::load_from(&Settings::path())
+    }
+
+    // A file that can't be used is moved out of the way so the next save doesn't destroy it
+    fn load_from(path: &Path) -> Self {
+        let Ok(text) = fs::read_to_string(path) else {
+            return Settings::default();
+        };
+
+        let problem = match Settings::parse(&text) {
+            Ok(settings) => return settings,
+            Err(problem) => problem
+        };
+        let backup = path.with_extension("toml.bak");
+        eprintln!("[ERROR::SETTINGS::INVALID] {}: {}, using defaults and keeping the file as {}", path.display(), problem, backup.display());
+        let _ = fs::rename(path, &backup);
+        Settings::default()
+    }
+
+    fn parse(text: &str) -> Result<Self, String> {
+        match toml::from_str::<Settings>(text) {
+            Ok(settings) if settings.version == SCHEMA_VERSION => Ok(settings),
+            Ok(settings) => Err(format!("unsupported version {}, expected {}", settings.version, SCHEMA_VERSION)),
+            Err(error) => Err(error.to_string())
+        }
+    }
+
+    // Written next to the real file first, so a crash halfway leaves the old settings intact
+    pub fn save(&self) -> Result<(), String> {
+        let path = Settings::path();
+        fs::create_dir_all(config_dir()).map_err(|error| error.to_string())?;
+        let text = toml::to_string(self).map_err(|error| error.to_string())?;
+        let temporary = path.with_extension("toml.tmp");
+        fs::write(&temporary, text).map_err(|error| error.to_string())?;
+        fs::rename(&temporary, &path).map_err(|error| error.to_string())
+    }
+
+    pub fn capture(toolbox: &Toolbox, pages: &Pages) -> Self {
+        let Toolbox { kind, registry, color, brush_size, recognizer, laser, spotlight, bucket, eyedropper, redactor, gradient, callout, symmetry, grid, background, .. } = toolbox;
+        Settings {
+            version: SCHEMA_VERSION,
+            brush: BrushSettings { color: Some(*color.as_array()), size: Some(*brush_size), history_size: Some(pages.current().max_undos), tool: Some(*kind), brush_tool: Some(registry.selected_name().to_string()) },
+            laser: LaserSettings { enabled: Some(laser.enabled), duration: Some(laser.duration) },
+            shape_recognition: RecognizerSettings { enabled: Some(recognizer.enabled), threshold: Some(recognizer.threshold), hold: Some(recognizer.hold) },
+            fill: FillSettings { tolerance: Some(bucket.tolerance), through_edges: Some(bucket.through_edges) },
+            eyedropper: EyedropperSettings { radius: Some(eyedropper.radius) },
+            redact: RedactSettings { mode: Some(redactor.mode), strength: Some(redactor.strength) },
+            gradient: GradientSettings {
+                mode: Some(gradient.mode),
+                ink_only: Some(gradient.ink_only),
+                stops: Some(gradient.stops.iter().map(|stop| StopSettings { position: stop.position, color: *stop.color.as_array() }).collect())
+            },
+            spotlight: SpotlightSettings { enabled: Some(spotlight.enabled), shape: Some(spotlight.shape), size: Some(spotlight.size), aspect: Some(spotlight.aspect), opacity: Some(spotlight.opacity) },
+            grid: GridSettings { enabled: Some(grid.enabled), spacing: Some(grid.spacing), color: Some(*grid.color.as_array()), snap: Some(grid.snap) },
+            background: BackgroundSettings {
+                enabled: Some(background.enabled),
+                color: Some(*background.color.as_array()),
+                pattern: Some(background.pattern),
+                spacing: Some(background.spacing),
+                pattern_color: Some(*background.pattern_color.as_array())
+            },
+            symmetry: SymmetrySettings { mode: Some(symmetry.mode), folds: Some(symmetry.folds) },
+            callout: CalloutSettings {
+                background: Some(*callout.background.as_array()),
+                border: Some(*callout.border.as_array()),
+                text_color: Some(*callout.text_color.as_array()),
+                text_scale: Some(callout.text_scale)
+            }
+        }
+    }
+
+    // Values are clamped to what the GUI allows, so a hand edited file can't break anything
+    fn sanitized(&self) -> Self {
+        let mut settings = self.clone();
+        let brush = &mut settings.brush;
+        clamp_color(&mut brush.color);
+        brush.size = brush.size.map(|size| size.clamp(1, MAX_BRUSH_SIZE));
+        brush.history_size = brush.history_size.map(|history_size| history_size.clamp(1, 25));
+
+        settings.laser.duration = settings.laser.duration.map(|duration| duration.clamp(0.5, 10.0));
+
+        let recognizer = &mut settings.shape_recognition;
+        recognizer.threshold = recognizer.threshold.map(|threshold| threshold.clamp(0.0, 1.0));
+        recognizer.hold = recognizer.hold.map(|hold| hold.clamp(0.1, 2.0));
+
+        settings.fill.tolerance = settings.fill.tolerance.map(|tolerance| tolerance.clamp(0.0, 1.0));
+        settings.eyedropper.radius = settings.eyedropper.radius.map(|radius| radius.clamp(0, 20));
+        settings.redact.strength = settings.redact.strength.map(|strength| strength.clamp(2, 32));
+
+        let gradient = &mut settings.gradient;
+        gradient.stops = gradient.stops.take().filter(|stops| stops.len() >= 2 && stops.len() <= MAX_STOPS);
+        for stop in gradient.stops.iter_mut().flatten() {
+            stop.position = stop.position.clamp(0.0, 1.0);
+            stop.color = stop.color.map(|channel| channel.clamp(0.0, 1.0));
+        }
+
+        let spotlight = &mut settings.spotlight;
+        spotlight.size = spotlight.size.map(|size| size.max(1));
+        spotlight.aspect = spotlight.aspect.map(|aspect| aspect.clamp(0.25, 8.0));
+        spotlight.opacity = spotlight.opacity.map(|opacity| opacity.clamp(0.0, 1.0));
+
+        settings.grid.spacing = settings.grid.spacing.map(|spacing| spacing.max(2));
+        clamp_color(&mut settings.grid.color);
+
+        let background = &mut settings.background;
+        clamp_color(&mut background.color);
+        background.spacing = background.spacing.map(|spacing| spacing.max(4));
+        clamp_color(&mut background.pattern_color);
+
+        settings.symmetry.folds = settings.symmetry.folds.map(|folds| folds.clamp(2, 16));
+
+        let callout = &mut settings.callout;
+        clamp_color(&mut callout.background);
+        clamp_color(&mut callout.border);
+        clamp_color(&mut callout.text_color);
+        callout.text_scale = callout.text_scale.map(|scale| scale.clamp(1, 6));
+        settings
+    }
+
+    pub fn apply(&self, toolbox: &mut Toolbox, pages: &mut Pages) {
+        let settings = self.sanitized();
+        let Toolbox { kind, registry, color, brush_size, recognizer, laser, spotlight, bucket, eyedropper, redactor, gradient, callout, symmetry, grid, background, .. } = toolbox;
+        set_color(color, settings.brush.color);
+        set(brush_size, settings.brush.size);
+        if let Some(history_size) = settings.brush.history_size {
+            pages.set_max_undos(history_size);
+        }
+        set(kind, settings.brush.tool);
+        // A plugin that's gone leaves the brush selected
+        if let Some(name) = &settings.brush.brush_tool {
+            registry.select_name(name);
+        }
+
+        set(&mut laser.enabled, settings.laser.enabled);
+        set(&mut laser.duration, settings.laser.duration);
+
+        set(&mut recognizer.enabled, settings.shape_recognition.enabled);
+        set(&mut recognizer.threshold, settings.shape_recognition.threshold);
+        set(&mut recognizer.hold, settings.shape_recognition.hold);
+
+        set(&mut bucket.tolerance, settings.fill.tolerance);
+        set(&mut bucket.through_edges, settings.fill.through_edges);
+
+        set(&mut eyedropper.radius, settings.eyedropper.radius);
+
+        set(&mut redactor.mode, settings.redact.mode);
+        set(&mut redactor.strength, settings.redact.strength);
+
+        set(&mut gradient.mode, settings.gradient.mode);
+        set(&mut gradient.ink_only, settings.gradient.ink_only);
+        if let Some(stops) = &settings.gradient.stops {
+            gradient.stops = stops.iter().map(|stop| GradientStop { position: stop.position, color: *Vec4::from_array(&stop.color) }).collect();
+        }
+
+        set(&mut spotlight.enabled, settings.spotlight.enabled);
+        set(&mut spotlight.shape, settings.spotlight.shape);
+        set(&mut spotlight.size, settings.spotlight.size);
+        set(&mut spotlight.aspect, settings.spotlight.aspect);
+        set(&mut spotlight.opacity, settings.spotlight.opacity);
+
+        set(&mut grid.enabled, settings.grid.enabled);
+        set(&mut grid.spacing, settings.grid.spacing);
+        set_color(&mut grid.color, settings.grid.color);
+        set(&mut grid.snap, settings.grid.snap);
+
+        set(&mut background.enabled, settings.background.enabled);
+        set_color(&mut background.color, settings.background.color);
+        set(&mut background.pattern, settings.background.pattern);
+        set(&mut background.spacing, settings.background.spacing);
+        set_color(&mut background.pattern_color, settings.background.pattern_color);
+
+        set(&mut symmetry.mode, settings.symmetry.mode);
+        set(&mut symmetry.folds, settings.symmetry.folds);
+
+        set_color(&mut callout.background, settings.callout.background);
+        set_color(&mut callout.border, settings.callout.border);
+        set_color(&mut callout.text_color, settings.callout.text_color);
+        set(&mut callout.text_scale, settings.callout.text_scale);
+    }
+}
+
+fn set<T>(target: &mut T, value: Option<T>) {
+    if let Some(value) = value {
+        *target = value;
+    }
+}
+
+fn set_color(target: &mut Vec4, value: Option<[f32; 4]>) {
+    set(target, value.map(|color| *Vec4::from_array(&color)));
+}
+
+fn clamp_color(color: &mut Option<[f32; 4]>) {
+    *color = color.map(|color| color.map(|channel| channel.clamp(0.0, 1.0)));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn old_files_keep_the_defaults_for_missing_fields() {
+        let settings = Settings::parse("version = 1\n[brush]\nsize = 12\nunknown = true\n").unwrap();
+        assert_eq!(settings.brush.size, Some(12));
+        assert_eq!(settings.brush.color, None);
+        assert_eq!(settings.laser, LaserSettings::default());
+    }
+
+    #[test]
+    fn rejects_other_versions_and_invalid_files() {
+        assert_eq!(Settings::parse("version = 2"), Err(format!("unsupported version 2, expected {}", SCHEMA_VERSION)));
+        assert!(Settings::parse("[brush]\nsize = 12").is_err());
+        assert!(Settings::parse("version = 1\n[brush]\nsize = \"big\"").is_err());
+        assert!(Settings::parse("version = ").is_err());
+    }
+
+    #[test]
+    fn round_trips_through_toml() {
+        let mut settings = Settings { version: SCHEMA_VERSION, ..Settings::default() };
+        settings.brush.color = Some([0.5, 0.25, 1.0, 1.0]);
+        settings.brush.tool = Some(ToolKind::Gradient);
+        settings.gradient.stops = Some(vec![StopSettings { position: 0.0, color: [0.0; 4] }, StopSettings { position: 1.0, color: [1.0; 4] }]);
+        assert_eq!(Settings::parse(&toml::to_string(&settings).unwrap()), Ok(settings));
+    }
+
+    #[test]
+    fn clamps_values_to_the_gui_ranges() {
+        let mut settings = Settings::default();
+        settings.brush.size = Some(MAX_BRUSH_SIZE + 100);
+        settings.brush.history_size = Some(0);
+        settings.brush.color = Some([2.0, -1.0, 0.5, 1.0]);
+        settings.symmetry.folds = Some(100);
+        settings.spotlight.aspect = Some(0.0);
+        settings.gradient.stops = Some(vec![StopSettings { position: 3.0, color: [1.0; 4] }]);
+
+        let sanitized = settings.sanitized();
+        assert_eq!(sanitized.brush.size, Some(MAX_BRUSH_SIZE));
+        assert_eq!(sanitized.brush.history_size, Some(1));
+        assert_eq!(sanitized.brush.color, Some([1.0, 0.0, 0.5, 1.0]));
+        assert_eq!(sanitized.symmetry.folds, Some(16));
+        assert_eq!(sanitized.spotlight.aspect, Some(0.25));
+        // A single stop isn't a gradient, the tool keeps its own
+        assert_eq!(sanitized.gradient.stops, None);
+        assert_eq!(sanitized.laser, LaserSettings::default());
+
+        settings.gradient.stops = Some(vec![StopSettings { position: -1.0, color: [0.0; 4] }, StopSettings { position: 3.0, color: [1.0; 4] }]);
+        let positions = settings.sanitized().gradient.stops.unwrap().iter().map(|stop| stop.position).collect::<Vec<_>>();
+        assert_eq!(positions, vec![0.0, 1.0]);
+    }
+
+    #[test]
+    fn invalid_files_are_kept_as_a_backup() {
+        let dir = std::env::temp_dir().join(format!("overdraw-settings-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("settings.toml");
+        let backup = dir.join("settings.toml.bak");
+
+        assert_eq!(Settings::load_from(&path), Settings::default());
+        assert!(!backup.exists());
+
+        fs::write(&path, "version = 1\n[brush]\nsize = 7\n").unwrap();
+        assert_eq!(Settings::load_from(&path).brush.size, Some(7));
+        assert!(path.exists());
+
+        fs::write(&path, "version = 0\n[brush]\nsize = 7\n").unwrap();
+        assert_eq!(Settings::load_from(&path), Settings::default());
+        assert!(!path.exists());
+        assert_eq!(fs::read_to_string(&backup).unwrap(), "version = 0\n[brush]\nsize = 7\n");
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
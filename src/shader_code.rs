@@ -15,16 +15,179 @@ pub static PIXEL_SHADER: &str = "
             #version 460 core\n
             uniform ivec2 cursor_pos;
             uniform ivec2 screen_size;
+            uniform vec2 view_offset;
+            uniform float view_zoom;
             uniform int brush_size;
+            // The canvas with the overlay on top, already drawn at screen size by TILE_SHADER
             uniform sampler2D tex;
+            uniform sampler2D laser;
+
+            uniform bool laser_mode;
+            uniform vec4 laser_color;
+
+            uniform bool spotlight;
+            uniform int spotlight_shape;
+            uniform vec2 spotlight_size;
+            uniform float spotlight_opacity;
+
+            uniform int symmetry_mode;
+            uniform vec2 symmetry_center;
+            uniform int symmetry_folds;
+
+            uniform bool grid;
+            uniform int grid_spacing;
+            uniform vec4 grid_color;
+
+            uniform bool background;
+            uniform vec4 background_color;
+            uniform int background_pattern;
+            uniform int background_spacing;
+            uniform vec4 background_pattern_color;
+
+            uniform bool ruler;
+            uniform vec2 ruler_start;
+            uniform vec2 ruler_end;
+
+            uniform bool floating;
+            uniform sampler2D selection;
+            uniform vec2 selection_center;
+            uniform vec2 selection_size;
+            uniform vec2 selection_scale;
+            uniform float selection_angle;
 
             in vec2 uv;
 
             out vec4 pixelColor;
+
+            vec4 over(vec4 src, vec4 dst)
+            {
+                float alpha = src.a + dst.a * (1.0 - src.a);
+                if (alpha <= 0.0) {
+                    return vec4(0.0);
+                }
+                return vec4((src.rgb * src.a + dst.rgb * dst.a * (1.0 - src.a)) / alpha, alpha);
+            }
+
+            vec4 floating_selection(vec2 pixel)
+            {
+                vec2 p = pixel - selection_center;
+                float c = cos(-selection_angle);
+                float s = sin(-selection_angle);
+                vec2 local = vec2(c * p.x - s * p.y, s * p.x + c * p.y);
+                vec2 half_size = selection_size * selection_scale * 0.5;
+                vec2 corner = abs(local) - half_size;
+
+                // Scale and rotate handles
+                if (max(abs(corner.x), abs(corner.y)) < 5.0 || length(local - vec2(0.0, half_size.y + 20.0)) < 5.0) {
+                    return vec4(1.0);
+                }
+                if (max(corner.x, corner.y) > 0.0) {
+                    return vec4(0.0);
+                }
+                if (max(corner.x, corner.y) > -1.0) {
+                    return vec4(0.3, 0.6, 1.0, 1.0);
+                }
+                return texture(selection, (local / selection_scale + selection_size * 0.5) / selection_size);
+            }
+
+            // 1 mirrors along a vertical axis, 2 along a horizontal one and 3 is radial
+            bool symmetry_guide(vec2 pixel)
+            {
+                vec2 d = pixel - symmetry_center;
+                bool dashed = mod(length(d), 12.0) < 6.0;
+                if (symmetry_mode == 1) {
+                    return abs(d.x) < 0.5 && dashed;
+                }
+                if (symmetry_mode == 2) {
+                    return abs(d.y) < 0.5 && dashed;
+                }
+                if (symmetry_mode == 3) {
+                    float sector = 6.28318530718 / float(symmetry_folds);
+                    float angle = atan(d.y, d.x);
+                    return (abs(length(d) * sin(angle - round(angle / sector) * sector)) < 0.5 && dashed) || abs(length(d) - 4.0) < 0.5;
+                }
+                return false;
+            }
+
+            bool grid_line(vec2 pixel)
+            {
+                vec2 m = mod(pixel, float(grid_spacing));
+                return m.x < 1.0 || m.y < 1.0;
+            }
+
+            // 1 is ruled, 2 dotted and 3 graph paper with a heavier line every fifth one
+            vec4 background_paper(vec2 pixel)
+            {
+                float spacing = float(background_spacing);
+                vec2 m = mod(pixel, spacing);
+                vec2 major = mod(pixel, spacing * 5.0);
+                float strength = 0.0;
+                if (background_pattern == 1 && m.y < 1.0) {
+                    strength = 1.0;
+                } else if (background_pattern == 2 && length(min(m, spacing - m)) < 1.5) {
+                    strength = 1.0;
+                } else if (background_pattern == 3 && (m.x < 1.0 || m.y < 1.0)) {
+                    strength = major.x < 1.0 || major.y < 1.0 ? 1.0 : 0.5;
+                }
+                return over(vec4(background_pattern_color.rgb, background_pattern_color.a * strength), background_color);
+            }
+
+            // Keep the sizes in sync with guides.rs
+            vec4 ruler_color(vec2 pixel)
+            {
+                float len = length(ruler_end - ruler_start);
+                vec2 dir = len > 0.0 ? (ruler_end - ruler_start) / len : vec2(1.0, 0.0);
+                vec2 d = pixel - ruler_start;
+                float along = dot(d, dir);
+                float across = dot(d, vec2(-dir.y, dir.x));
+
+                if (length(pixel - ruler_start) < 8.0 || length(pixel - ruler_end) < 8.0) {
+                    return vec4(1.0, 1.0, 1.0, 0.9);
+                }
+                if (along < 0.0 || along > len || across < -1.0 || across > 30.0) {
+                    return vec4(0.0);
+                }
+                // Edge and tick marks every 10 pixels, longer ones every 50
+                float tick = mod(along, 50.0) < 1.0 ? 12.0 : (mod(along, 10.0) < 1.0 ? 6.0 : 0.0);
+                if (across < 1.0 || across < tick) {
+                    return vec4(0.1, 0.1, 0.1, 0.9);
+                }
+                return vec4(1.0, 0.95, 0.7, 0.5);
+            }
+
             void main()
             {
-                pixelColor = length(cursor_pos - uv * screen_size) < brush_size &&
-                             length(cursor_pos - uv * screen_size) > brush_size - 1 ? vec4(1) : texture(tex, uv);
+                // Everything but the laser lives in canvas texels
+                vec2 pixel = view_offset + uv * screen_size / view_zoom;
+                pixelColor = texture(tex, uv);
+                if (grid && grid_line(pixel)) {
+                    pixelColor = over(pixelColor, grid_color);
+                }
+                if (background) {
+                    pixelColor = over(pixelColor, background_paper(pixel));
+                }
+                pixelColor = over(texture(laser, uv), pixelColor);
+                if (floating) {
+                    pixelColor = over(floating_selection(pixel), pixelColor);
+                }
+                if (ruler) {
+                    pixelColor = over(ruler_color(pixel), pixelColor);
+                }
+                if (symmetry_guide(pixel)) {
+                    pixelColor = over(vec4(0.3, 0.8, 1.0, 0.6), pixelColor);
+                }
+                if (spotlight) {
+                    // 0 is a circle, 1 a rectangle, the hole gets a soft edge of two pixels
+                    vec2 d = abs(pixel - cursor_pos);
+                    float outside = spotlight_shape == 0 ? length(d) - spotlight_size.y : max(d.x - spotlight_size.x, d.y - spotlight_size.y);
+                    pixelColor = over(vec4(0.0, 0.0, 0.0, spotlight_opacity * smoothstep(-1.0, 1.0, outside)), pixelColor);
+                }
+                if (laser_mode) {
+                    float glow = clamp(1.0 - length(cursor_pos - pixel) / brush_size, 0.0, 1.0);
+                    pixelColor = over(vec4(laser_color.rgb, glow * glow), pixelColor);
+                } else if (length(cursor_pos - pixel) < brush_size && length(cursor_pos - pixel) > brush_size - 1) {
+                    pixelColor = vec4(1);
+                }
             }
             \0";
 
@@ -37,14 +200,19 @@ uniform ivec2 cursor_pos;
 uniform ivec2 prev_cursor_pos;
 // uniform ivec2 screen_size;
 uniform int brush_size;
+// Canvas texels of the region and where the image starts, see tiles.rs
+uniform ivec2 offset;
+uniform ivec2 end;
+uniform ivec2 origin;
 
 uniform vec4 color;
 
 void main() {
     //vec2 uv = vec2(0.0, 0.0);
-    int minX = min(prev_cursor_pos.x, cursor_pos.x);
-    int minY = min(prev_cursor_pos.y, cursor_pos.y);
-    ivec2 screen_coord = ivec2(gl_GlobalInvocationID.xy + ivec2(minX - min(brush_size, minX), minY - min(brush_size, minY)));
+    ivec2 screen_coord = ivec2(gl_GlobalInvocationID.xy) + offset;
+    if (any(greaterThanEqual(screen_coord, end))) {
+        return;
+    }
 
     //uv.x = float(screen_coord.x) / (gl_NumWorkGroups.x * gl_WorkGroupSize.x);
     //uv.y = float(screen_coord.y) / (gl_NumWorkGroups.y * gl_WorkGroupSize.y);
@@ -72,7 +240,225 @@ void main() {
     length(screen_coord - cursor_pos) < brush_size ||
     length(screen_coord - prev_cursor_pos) < brush_size)
     {
-        imageStore(imgOutput, screen_coord, color);
+        imageStore(imgOutput, screen_coord - origin, color);
+    }
+}
+\0";
+
+pub static STAMP_SHADER: &str = "
+                #version 460\n
+layout (local_size_x = 10, local_size_y = 10, local_size_z = 1) in;
+
+layout(rgba32f, binding = 0) uniform image2D imgOutput;
+uniform sampler2D selection;
+uniform ivec2 offset;
+uniform ivec2 end;
+uniform ivec2 origin;
+uniform vec2 center;
+uniform vec2 size;
+uniform vec2 scale;
+uniform float angle;
+
+void main() {
+    ivec2 screen_coord = ivec2(gl_GlobalInvocationID.xy) + offset;
+    if (any(greaterThanEqual(screen_coord, end))) {
+        return;
+    }
+
+    // Map the canvas pixel back into the untransformed selection
+    vec2 p = vec2(screen_coord) + 0.5 - center;
+    float c = cos(-angle);
+    float s = sin(-angle);
+    vec2 uv = (vec2(c * p.x - s * p.y, s * p.x + c * p.y) / scale + size * 0.5) / size;
+    if (any(lessThan(uv, vec2(0.0))) || any(greaterThanEqual(uv, vec2(1.0)))) {
+        return;
+    }
+
+    vec4 src = texture(selection, uv);
+    vec4 dst = imageLoad(imgOutput, screen_coord - origin);
+    float alpha = src.a + dst.a * (1.0 - src.a);
+    if (alpha > 0.0) {
+        imageStore(imgOutput, screen_coord - origin, vec4((src.rgb * src.a + dst.rgb * dst.a * (1.0 - src.a)) / alpha, alpha));
+    }
+}
+\0";
+
+pub static REDACT_SHADER: &str = "
+                #version 460\n
+layout (local_size_x = 10, local_size_y = 10, local_size_z = 1) in;
+
+layout(rgba32f, binding = 0) uniform image2D imgOutput;
+// The pixels around the rectangle, read back before any of it changed
+uniform sampler2D source;
+uniform ivec2 source_origin;
+uniform ivec2 rect_min;
+uniform ivec2 rect_size;
+uniform ivec2 offset;
+uniform ivec2 end;
+uniform ivec2 origin;
+// 0: pixelate, 1: blur, 2: black box
+uniform int mode;
+uniform int strength;
+
+vec4 average(vec4 sum) {
+    return sum.a > 0.0 ? vec4(sum.rgb / sum.a, sum.a) : vec4(0.0);
+}
+
+void main() {
+    ivec2 screen_coord = ivec2(gl_GlobalInvocationID.xy) + offset;
+    if (any(greaterThanEqual(screen_coord, end))) {
+        return;
+    }
+    ivec2 local = screen_coord - rect_min;
+    ivec2 bounds = textureSize(source, 0) - 1;
+
+    vec4 color = vec4(0.0, 0.0, 0.0, 1.0);
+    if (mode == 0) {
+        // Every block gets the average of the pixels it covers
+        ivec2 block_start = rect_min + (local / strength) * strength;
+        ivec2 block_end = min(block_start + strength, rect_min + rect_size);
+        vec4 sum = vec4(0.0);
+        for (int y = block_start.y; y < block_end.y; y++) {
+            for (int x = block_start.x; x < block_end.x; x++) {
+                vec4 texel = texelFetch(source, ivec2(x, y) - source_origin, 0);
+                sum += vec4(texel.rgb * texel.a, texel.a);
+            }
+        }
+        vec2 count = vec2(block_end - block_start);
+        color = average(sum);
+        color.a = sum.a / (count.x * count.y);
+    } else if (mode == 1) {
+        float sigma = max(float(strength) / 2.0, 0.5);
+        vec4 sum = vec4(0.0);
+        float weights = 0.0;
+        for (int y = -strength; y <= strength; y++) {
+            for (int x = -strength; x <= strength; x++) {
+                float weight = exp(-float(x * x + y * y) / (2.0 * sigma * sigma));
+                vec4 texel = texelFetch(source, clamp(screen_coord + ivec2(x, y) - source_origin, ivec2(0), bounds), 0);
+                sum += vec4(texel.rgb * texel.a, texel.a) * weight;
+                weights += weight;
+            }
+        }
+        color = average(sum);
+        color.a = sum.a / weights;
+    }
+    imageStore(imgOutput, screen_coord - origin, color);
+}
+\0";
+
+pub static GRADIENT_SHADER: &str = "
+                #version 460\n
+layout (local_size_x = 10, local_size_y = 10, local_size_z = 1) in;
+
+layout(rgba32f, binding = 0) uniform image2D imgOutput;
+uniform vec2 start;
+uniform vec2 end;
+uniform bool radial;
+uniform bool ink_only;
+uniform ivec2 offset;
+uniform ivec2 end;
+uniform ivec2 origin;
+
+// Keep in sync with MAX_STOPS in gradient.rs
+uniform int stop_count;
+uniform vec4 stop_colors[8];
+uniform float stop_positions[8];
+
+vec4 gradient(float t) {
+    if (t <= stop_positions[0]) {
+        return stop_colors[0];
+    }
+    for (int i = 1; i < stop_count; i++) {
+        if (t <= stop_positions[i]) {
+            float f = (t - stop_positions[i - 1]) / max(stop_positions[i] - stop_positions[i - 1], 0.00001);
+            return mix(stop_colors[i - 1], stop_colors[i], f);
+        }
+    }
+    return stop_colors[stop_count - 1];
+}
+
+void main() {
+    ivec2 screen_coord = ivec2(gl_GlobalInvocationID.xy) + offset;
+    if (any(greaterThanEqual(screen_coord, end))) {
+        return;
+    }
+
+    vec2 p = vec2(screen_coord) + 0.5 - start;
+    vec2 dir = end - start;
+    float t = radial ? length(p) / max(length(dir), 0.00001) : dot(p, dir) / max(dot(dir, dir), 0.00001);
+    vec4 src = gradient(clamp(t, 0.0, 1.0));
+    vec4 dst = imageLoad(imgOutput, screen_coord - origin);
+
+    if (ink_only) {
+        // Recolors the ink but keeps its coverage
+        if (dst.a > 0.0) {
+            imageStore(imgOutput, screen_coord - origin, vec4(src.rgb, dst.a * src.a));
+        }
+        return;
+    }
+    float alpha = src.a + dst.a * (1.0 - src.a);
+    if (alpha > 0.0) {
+        imageStore(imgOutput, screen_coord - origin, vec4((src.rgb * src.a + dst.rgb * dst.a * (1.0 - src.a)) / alpha, alpha));
+    }
+}
+\0";
+
+pub static DIFF_SHADER: &str = "
+                #version 460\n
+layout (local_size_x = 10, local_size_y = 10, local_size_z = 1) in;
+
+// One texel per pair of tiles, set to 1 wherever a pixel of the two differs
+layout(rgba32f, binding = 0) uniform image2D imgOutput;
+uniform sampler2D before;
+uniform sampler2D after;
+uniform ivec2 flag;
+
+void main() {
+    ivec2 screen_coord = ivec2(gl_GlobalInvocationID.xy);
+    if (any(greaterThanEqual(screen_coord, textureSize(after, 0)))) {
+        return;
+    }
+    if (texelFetch(before, screen_coord, 0) != texelFetch(after, screen_coord, 0)) {
+        imageStore(imgOutput, flag, vec4(1.0));
+    }
+}
+\0";
+
+pub static TILE_SHADER: &str = "
+                #version 460\n
+layout (local_size_x = 10, local_size_y = 10, local_size_z = 1) in;
+
+// Draws a tile into a screen sized image, offset and end are the screen pixels it covers
+layout(rgba32f, binding = 0) uniform image2D imgOutput;
+uniform sampler2D tile;
+uniform vec2 tile_origin;
+uniform ivec2 offset;
+uniform ivec2 end;
+uniform vec2 view_offset;
+uniform float view_zoom;
+uniform bool blend;
+
+void main() {
+    ivec2 screen_coord = ivec2(gl_GlobalInvocationID.xy) + offset;
+    if (any(greaterThanEqual(screen_coord, end))) {
+        return;
+    }
+
+    // Same mapping from screen pixels to canvas texels as the pixel shader. Fetched instead of filtered,
+    // filtering would clamp at the tile's edge and show the seams between tiles
+    ivec2 texel = ivec2(floor(view_offset + (vec2(screen_coord) + 0.5) / view_zoom - tile_origin));
+    if (any(lessThan(texel, ivec2(0))) || any(greaterThanEqual(texel, textureSize(tile, 0)))) {
+        return;
+    }
+    vec4 src = texelFetch(tile, texel, 0);
+    if (!blend) {
+        imageStore(imgOutput, screen_coord, src);
+        return;
+    }
+    vec4 dst = imageLoad(imgOutput, screen_coord);
+    float alpha = src.a + dst.a * (1.0 - src.a);
+    if (alpha > 0.0) {
+        imageStore(imgOutput, screen_coord, vec4((src.rgb * src.a + dst.rgb * dst.a * (1.0 - src.a)) / alpha, alpha));
     }
 }
-\0";
\ No newline at end of file
+\0";
@@ -0,0 +1,314 @@
+use std::time::Instant;
+use glm::*;
+
+// Mean distance of the stroke to a shape, relative to the stroke's diagonal, at which confidence reaches zero
+const TOLERANCE: f32 = 0.1;
+// Strokes that end closer to their start than this fraction of their length count as closed
+const CLOSED_GAP: f32 = 0.2;
+const MIN_SIZE: f32 = 10.0;
+const ELLIPSE_SEGMENTS: usize = 64;
+// Arrow heads are drawn back from the tip, head strokes can't reach farther than this fraction of the shaft
+const ARROW_HEAD: f32 = 0.2;
+const ARROW_ANGLE: f32 = 0.5;
+const MAX_ARROW_HEAD: f32 = 0.5;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Shape {
+    Line(Vec2, Vec2),
+    // min and max corner
+    Rectangle(Vec2, Vec2),
+    // center and radii
+    Ellipse(Vec2, Vec2),
+    Triangle(Vec2, Vec2, Vec2),
+    // tail and tip
+    Arrow(Vec2, Vec2)
+}
+
+impl Shape {
+    // Polyline that traces the shape, closed shapes end where they start
+    pub fn outline(&self) -> Vec<Vec2> {
+        match *self {
+            Shape::Line(a, b) => vec![a, b],
+            Shape::Rectangle(a, b) => vec![a, vec2(b.x, a.y), b, vec2(a.x, b.y), a],
+            Shape::Ellipse(center, radii) => (0..=ELLIPSE_SEGMENTS).map(|i| {
+                let angle = i as f32 / ELLIPSE_SEGMENTS as f32 * std::f32::consts::TAU;
+                center + vec2(angle.cos() * radii.x, angle.sin() * radii.y)
+            }).collect(),
+            Shape::Triangle(a, b, c) => vec![a, b, c, a],
+            Shape::Arrow(tail, tip) => {
+                let [left, right] = arrow_barbs(tail, tip);
+                vec![tail, tip, left, tip, right]
+            }
+        }
+    }
+
+    // Moves the points that define the shape, used to snap it to the grid
+    pub fn map_points(&self, f: impl Fn(Vec2) -> Vec2) -> Shape {
+        match *self {
+            Shape::Line(a, b) => Shape::Line(f(a), f(b)),
+            Shape::Rectangle(a, b) => Shape::Rectangle(f(a), f(b)),
+            Shape::Ellipse(center, radii) => {
+                let (a, b) = (f(center - radii), f(center + radii));
+                Shape::Ellipse((a + b) * 0.5, abs(b - a) * 0.5)
+            }
+            Shape::Triangle(a, b, c) => Shape::Triangle(f(a), f(b), f(c)),
+            Shape::Arrow(tail, tip) => Shape::Arrow(f(tail), f(tip))
+        }
+    }
+
+    pub fn distance(&self, p: Vec2) -> f32 {
+        match *self {
+            Shape::Line(a, b) => segment_distance(p, a, b),
+            Shape::Rectangle(a, b) => {
+                let center = (a + b) * 0.5;
+                let d = abs(p - center) - abs(b - a) * 0.5;
+                if d.x > 0.0 || d.y > 0.0 {
+                    length(max(d, vec2(0.0, 0.0)))
+                } else {
+                    -d.x.max(d.y)
+                }
+            }
+            Shape::Ellipse(center, radii) => {
+                let p = p - center;
+                let k = length(vec2(p.x / radii.x, p.y / radii.y));
+                if k <= 0.0 {
+                    radii.x.min(radii.y)
+                } else {
+                    (k - 1.0).abs() * length(p) / k
+                }
+            }
+            Shape::Triangle(a, b, c) => segment_distance(p, a, b).min(segment_distance(p, b, c)).min(segment_distance(p, c, a)),
+            Shape::Arrow(tail, tip) => {
+                let [left, right] = arrow_barbs(tail, tip);
+                segment_distance(p, tail, tip).min(segment_distance(p, tip, left)).min(segment_distance(p, tip, right))
+            }
+        }
+    }
+}
+
+// Best fitting shape for a freehand stroke together with a confidence between 0 and 1
+pub fn fit(points: &[Vec2]) -> Option<(Shape, f32)> {
+    if points.len() < 3 {
+        return None;
+    }
+
+    let min_corner = points.iter().fold(points[0], |a, b| min(a, *b));
+    let max_corner = points.iter().fold(points[0], |a, b| max(a, *b));
+    let diagonal = length(max_corner - min_corner);
+    if diagonal < MIN_SIZE {
+        return None;
+    }
+
+    let first = points[0];
+    let last = points[points.len() - 1];
+    let path_length: f32 = points.windows(2).map(|segment| length(segment[1] - segment[0])).sum();
+
+    let mut candidates = vec![Shape::Line(first, last)];
+    if length(last - first) < CLOSED_GAP * path_length {
+        let center = (min_corner + max_corner) * 0.5;
+        candidates.push(Shape::Rectangle(min_corner, max_corner));
+        candidates.push(Shape::Ellipse(center, max((max_corner - min_corner) * 0.5, vec2(1.0, 1.0))));
+        candidates.push(triangle(points));
+    } else if let Some(arrow) = arrow(points) {
+        candidates.push(arrow);
+    }
+
+    candidates.into_iter()
+        .map(|shape| {
+            let error = points.iter().map(|p| shape.distance(*p)).sum::<f32>() / points.len() as f32 / diagonal;
+            (shape, (1.0 - error / TOLERANCE).clamp(0.0, 1.0))
+        })
+        .max_by(|a, b| a.1.total_cmp(&b.1))
+}
+
+pub fn recognize(points: &[Vec2], threshold: f32) -> Option<Shape> {
+    fit(points).filter(|(_, confidence)| *confidence >= threshold).map(|(shape, _)| shape)
+}
+
+// Captures the brush stroke and hands out a clean shape once the cursor was held still at the end of it
+pub struct Recognizer {
+    pub enabled: bool,
+    pub threshold: f32,
+    pub hold: f32,
+    points: Vec<Vec2>,
+    still_since: Instant,
+    snapped: bool
+}
+
+impl Recognizer {
+    pub fn new() -> Self {
+        Recognizer { enabled: false, threshold: 0.6, hold: 0.5, points: Vec::new(), still_since: Instant::now(), snapped: false }
+    }
+
+    pub fn begin(&mut self, p: Vec2) {
+        self.points = vec![p];
+        self.still_since = Instant::now();
+        self.snapped = false;
+    }
+
+    pub fn add_point(&mut self, p: Vec2) {
+        if self.points.last() != Some(&p) {
+            self.points.push(p);
+            self.still_since = Instant::now();
+        }
+    }
+
+    pub fn poll(&mut self) -> Option<Shape> {
+        if !self.enabled || self.snapped || self.still_since.elapsed().as_secs_f32() < self.hold {
+            return None;
+        }
+        let shape = recognize(&self.points, self.threshold);
+        self.snapped = shape.is_some();
+        shape
+    }
+
+    // The stroke was replaced, the rest of it is ignored
+    pub fn is_snapped(&self) -> bool {
+        self.snapped
+    }
+}
+
+impl Default for Recognizer {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+fn segment_distance(p: Vec2, a: Vec2, b: Vec2) -> f32 {
+    let ab = b - a;
+    let len = dot(ab, ab);
+    if len <= 0.0 {
+        return length(p - a);
+    }
+    let t = (dot(p - a, ab) / len).clamp(0.0, 1.0);
+    length(p - (a + ab * t))
+}
+
+// A shaft from the start to the point farthest from it, then head strokes that stay near that tip and go back
+fn arrow(points: &[Vec2]) -> Option<Shape> {
+    let tail = points[0];
+    let (tip_index, tip) = points.iter().enumerate().max_by(|a, b| length(*a.1 - tail).total_cmp(&length(*b.1 - tail)))?;
+    let head = &points[tip_index + 1..];
+    let shaft = length(*tip - tail);
+    let head_length: f32 = points[tip_index..].windows(2).map(|segment| length(segment[1] - segment[0])).sum();
+    if head.is_empty() || head_length < ARROW_HEAD * 0.5 * shaft {
+        return None;
+    }
+    let direction = (*tip - tail) / shaft;
+    let near_tip = head.iter().all(|p| length(*p - *tip) < MAX_ARROW_HEAD * shaft && dot(*p - *tip, direction) < 0.0);
+    near_tip.then_some(Shape::Arrow(tail, *tip))
+}
+
+fn arrow_barbs(tail: Vec2, tip: Vec2) -> [Vec2; 2] {
+    let back = (tail - tip) * ARROW_HEAD;
+    let rotate = |v: Vec2, angle: f32| vec2(v.x * angle.cos() - v.y * angle.sin(), v.x * angle.sin() + v.y * angle.cos());
+    [tip + rotate(back, ARROW_ANGLE), tip + rotate(back, -ARROW_ANGLE)]
+}
+
+// Corners of the largest triangle spanned by the stroke
+fn triangle(points: &[Vec2]) -> Shape {
+    let centroid = points.iter().fold(vec2(0.0, 0.0), |a, b| a + *b) / points.len() as f32;
+    let farthest = |from: Vec2| *points.iter().max_by(|a, b| length(**a - from).total_cmp(&length(**b - from))).unwrap();
+
+    let a = farthest(centroid);
+    let b = farthest(a);
+    let area = |p: Vec2| ((b.x - a.x) * (p.y - a.y) - (b.y - a.y) * (p.x - a.x)).abs();
+    let c = *points.iter().max_by(|p, q| area(**p).total_cmp(&area(**q))).unwrap();
+    Shape::Triangle(a, b, c)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Points along a polyline, a few pixels apart like a mouse drag, pushed sideways by a repeating wobble
+    fn record(corners: &[Vec2], wobble: f32) -> Vec<Vec2> {
+        let mut points = Vec::new();
+        for segment in corners.windows(2) {
+            let steps = (length(segment[1] - segment[0]) / 4.0).ceil().max(1.0) as usize;
+            for i in 0..steps {
+                points.push(segment[0] + (segment[1] - segment[0]) * (i as f32 / steps as f32));
+            }
+        }
+        points.push(corners[corners.len() - 1]);
+        points.iter().enumerate().map(|(i, p)| *p + vec2(0.0, [0.0, wobble, 0.0, -wobble][i % 4])).collect()
+    }
+
+    fn circle(center: Vec2, radius: f32) -> Vec<Vec2> {
+        (0..=48).map(|i| {
+            let angle = i as f32 / 48.0 * std::f32::consts::TAU;
+            center + vec2(angle.cos(), angle.sin()) * radius
+        }).collect()
+    }
+
+    #[test]
+    fn recognizes_a_wobbly_line() {
+        let points = record(&[vec2(0.0, 0.0), vec2(200.0, 100.0)], 2.0);
+        assert!(matches!(recognize(&points, 0.6), Some(Shape::Line(..))));
+    }
+
+    #[test]
+    fn recognizes_a_rectangle() {
+        let points = record(&[vec2(10.0, 10.0), vec2(210.0, 10.0), vec2(210.0, 110.0), vec2(10.0, 110.0), vec2(10.0, 14.0)], 1.5);
+        let Some(Shape::Rectangle(min_corner, max_corner)) = recognize(&points, 0.6) else {
+            panic!("expected a rectangle");
+        };
+        assert!(length(min_corner - vec2(10.0, 8.5)) < 3.0 && length(max_corner - vec2(210.0, 111.5)) < 3.0);
+    }
+
+    #[test]
+    fn recognizes_an_ellipse() {
+        let points = circle(vec2(100.0, 100.0), 80.0);
+        let Some(Shape::Ellipse(center, radii)) = recognize(&points, 0.6) else {
+            panic!("expected an ellipse");
+        };
+        assert!(length(center - vec2(100.0, 100.0)) < 2.0 && length(radii - vec2(80.0, 80.0)) < 2.0);
+    }
+
+    #[test]
+    fn recognizes_a_triangle() {
+        let points = record(&[vec2(0.0, 0.0), vec2(200.0, 0.0), vec2(100.0, 170.0), vec2(4.0, 4.0)], 1.0);
+        assert!(matches!(recognize(&points, 0.6), Some(Shape::Triangle(..))));
+    }
+
+    #[test]
+    fn recognizes_an_arrow() {
+        // Shaft to the right, then one barb and back to the tip for the other
+        let points = record(&[vec2(0.0, 0.0), vec2(200.0, 0.0), vec2(165.0, 20.0), vec2(200.0, 0.0), vec2(165.0, -20.0)], 1.0);
+        let Some(Shape::Arrow(tail, tip)) = recognize(&points, 0.6) else {
+            panic!("expected an arrow");
+        };
+        assert!(length(tail - vec2(0.0, 0.0)) < 2.0 && length(tip - vec2(200.0, 0.0)) < 2.0);
+    }
+
+    #[test]
+    fn a_hook_is_not_an_arrow() {
+        // Goes back along the shaft much farther than a head would
+        let points = record(&[vec2(0.0, 0.0), vec2(200.0, 0.0), vec2(20.0, 30.0)], 0.0);
+        assert!(!matches!(fit(&points), Some((Shape::Arrow(..), _))));
+    }
+
+    #[test]
+    fn scribbles_miss_the_threshold() {
+        let points = record(&[vec2(0.0, 0.0), vec2(150.0, 120.0), vec2(10.0, 150.0), vec2(160.0, 10.0), vec2(40.0, 60.0), vec2(120.0, 140.0)], 0.0);
+        assert_eq!(recognize(&points, 0.6), None);
+    }
+
+    #[test]
+    fn threshold_decides_on_a_near_miss() {
+        // A lumpy circle still fits an ellipse best, but only with moderate confidence
+        let points: Vec<Vec2> = circle(vec2(0.0, 0.0), 100.0).into_iter().enumerate()
+            .map(|(i, p)| p * if i % 2 == 0 { 1.0 } else { 0.9 }).collect();
+        let (shape, confidence) = fit(&points).unwrap();
+        assert!(matches!(shape, Shape::Ellipse(..)));
+        assert!(confidence > 0.0 && confidence < 1.0);
+        assert_eq!(recognize(&points, confidence + 0.01), None);
+        assert_eq!(recognize(&points, confidence), Some(shape));
+    }
+
+    #[test]
+    fn tiny_and_short_strokes_are_ignored() {
+        assert_eq!(fit(&[vec2(0.0, 0.0), vec2(1.0, 1.0)]), None);
+        assert_eq!(fit(&record(&[vec2(0.0, 0.0), vec2(3.0, 2.0), vec2(5.0, 0.0)], 0.0)), None);
+    }
+}
@@ -0,0 +1,31 @@
+use serde::{Deserialize, Serialize};
+use crate::open_gl::shaders::StandardShader;
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SpotlightShape {
+    Circle,
+    Rectangle
+}
+
+// Dims everything but a hole around the cursor, only exists in the screen pass
+pub struct Spotlight {
+    pub enabled: bool,
+    pub shape: SpotlightShape,
+    pub size: i32,
+    pub aspect: f32,
+    pub opacity: f32
+}
+
+impl Spotlight {
+    pub fn new() -> Self {
+        Spotlight { enabled: false, shape: SpotlightShape::Circle, size: 150, aspect: 2.0, opacity: 0.7 }
+    }
+
+    pub fn bind(&self, shader: &StandardShader) {
+        shader.set_int(self.enabled as i32, "spotlight");
+        shader.set_int(self.shape as i32, "spotlight_shape");
+        shader.set_vector2(&glm::vec2(self.size as f32 * self.aspect, self.size as f32), "spotlight_size");
+        shader.set_float(self.opacity, "spotlight_opacity");
+    }
+}
@@ -0,0 +1,58 @@
+use glm::*;
+use imgui_glfw_rs::imgui::{im_str, Ui};
+use crate::tools::{Tool, ToolContext};
+
+// Airbrush that scatters dots inside the brush size for as long as the button is held,
+// also the example for tools that are compiled in behind a feature
+pub struct Spray {
+    density: i32,
+    dot_size: i32,
+    seed: u32
+}
+
+impl Spray {
+    pub fn new() -> Self {
+        Spray { density: 12, dot_size: 1, seed: 0x9e3779b9 }
+    }
+
+    // Xorshift, good enough for scattering dots
+    fn random(&mut self) -> f32 {
+        self.seed ^= self.seed << 13;
+        self.seed ^= self.seed >> 17;
+        self.seed ^= self.seed << 5;
+        self.seed as f32 / u32::MAX as f32
+    }
+}
+
+impl Default for Spray {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Tool for Spray {
+    fn name(&self) -> &str {
+        "spray"
+    }
+
+    fn press(&mut self, _button: usize, context: &mut ToolContext) {
+        context.begin();
+    }
+
+    fn drag(&mut self, _button: usize, context: &mut ToolContext) {
+        let center = context.guides.guide(context.cursor);
+        for _ in 0..self.density {
+            // Square root keeps the dots evenly spread instead of bunched up in the middle
+            let radius = self.random().sqrt() * *context.brush_size as f32;
+            let angle = self.random() * std::f32::consts::TAU;
+            let dot = center + vec2(angle.cos(), angle.sin()) * radius;
+            let color = *context.color;
+            context.paint_symmetric(color, dot, dot, self.dot_size);
+        }
+    }
+
+    fn settings(&mut self, ui: &Ui) {
+        ui.slider_int(im_str!("Density"), &mut self.density, 1, 64).build();
+        ui.slider_int(im_str!("Dot Size"), &mut self.dot_size, 1, 8).build();
+    }
+}
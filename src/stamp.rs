@@ -0,0 +1,122 @@
+use std::fs;
+use glm::*;
+use crate::brush::*;
+use crate::font;
+use crate::image::load_png;
+use crate::open_gl::shaders::ComputeShader;
+use crate::paths::config_dir;
+use crate::tiles::Surface;
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum StampKind {
+    Number,
+    Icon(usize)
+}
+
+pub struct Icon {
+    pub name: String,
+    width: u32,
+    height: u32,
+    // Tightly packed RGBA with the bottom row first
+    pixels: Vec<f32>
+}
+
+pub struct Stamps {
+    pub kind: StampKind,
+    pub counter: i32,
+    pub icons: Vec<Icon>
+}
+
+impl Stamps {
+    pub fn new() -> Self {
+        let mut stamps = Stamps { kind: StampKind::Number, counter: 1, icons: Vec::new() };
+        stamps.reload();
+        stamps
+    }
+
+    // Picks up the PNGs in the stamps folder of the config directory
+    pub fn reload(&mut self) {
+        self.icons.clear();
+        let Ok(entries) = fs::read_dir(config_dir().join("stamps")) else {
+            return;
+        };
+
+        let mut paths: Vec<_> = entries.filter_map(|entry| entry.ok()).map(|entry| entry.path())
+            .filter(|path| path.extension().map_or(false, |extension| extension.eq_ignore_ascii_case("png")))
+            .collect();
+        paths.sort();
+        for path in paths {
+            match load_png(&path) {
+                Ok((width, height, pixels)) => {
+                    let name = path.file_stem().unwrap_or_default().to_string_lossy().into_owned();
+                    self.icons.push(Icon { name, width, height, pixels });
+                }
+                Err(error) => eprintln!("[ERROR::STAMP::LOADING_FAILED] {}: {}", path.display(), error)
+            }
+        }
+        if matches!(self.kind, StampKind::Icon(i) if i >= self.icons.len()) {
+            self.kind = StampKind::Number;
+        }
+    }
+
+    pub fn reset_counter(&mut self) {
+        self.counter = 1;
+    }
+
+    // Places the stamp and moves on to the next number
+    pub fn place(&mut self, compute_shader: &ComputeShader, surface: &dyn Surface, p: Vec2, color: Vec4, brush_size: i32) {
+        self.draw(compute_shader, surface, p, color, brush_size);
+        if self.kind == StampKind::Number {
+            self.counter += 1;
+        }
+    }
+
+    // Stamps are twice the brush size across so the numbers stay readable with thin brushes
+    pub fn draw(&self, compute_shader: &ComputeShader, surface: &dyn Surface, p: Vec2, color: Vec4, brush_size: i32) {
+        let radius = Ord::max(brush_size * 2, font::CELL_HEIGHT as i32);
+        match self.kind {
+            StampKind::Number => {
+                paint(compute_shader, surface, color, p, p, radius);
+
+                let text = self.counter.to_string();
+                let (text_width, _) = font::measure(&text, 1);
+                let scale = Ord::max(1, Ord::min(radius as u32 / font::CELL_HEIGHT, radius as u32 * 3 / 2 / text_width));
+                let (width, height) = font::measure(&text, scale);
+                // The measured size includes a spacing column on the right and a row at the bottom
+                let pos = p - vec2((width - scale) as f32, (height + scale) as f32) * 0.5;
+                draw_text(surface, &text, pos, scale, contrasting(color));
+            }
+            StampKind::Icon(i) => {
+                let Some(icon) = self.icons.get(i) else {
+                    return;
+                };
+                let (width, height, pixels) = icon.scaled(radius as u32 * 2);
+                blend_pixels(surface, (p.x - width as f32 * 0.5) as i32, (p.y - height as f32 * 0.5) as i32, width, height, &pixels);
+            }
+        }
+    }
+}
+
+impl Icon {
+    // Nearest neighbour resize so the longer side matches the given size
+    fn scaled(&self, size: u32) -> (u32, u32, Vec<f32>) {
+        let longest = Ord::max(self.width, self.height);
+        let width = Ord::max(1, self.width * size / longest);
+        let height = Ord::max(1, self.height * size / longest);
+
+        let mut pixels = Vec::with_capacity((width * height * 4) as usize);
+        for y in 0..height {
+            for x in 0..width {
+                let i = ((y * self.height / height * self.width + x * self.width / width) * 4) as usize;
+                pixels.extend_from_slice(&self.pixels[i..i + 4]);
+            }
+        }
+        (width, height, pixels)
+    }
+}
+
+// Black or white, whichever reads better on top of the color
+fn contrasting(color: Vec4) -> Vec4 {
+    let luminance = 0.2126 * color.x + 0.7152 * color.y + 0.0722 * color.z;
+    if luminance > 0.5 { vec4(0.0, 0.0, 0.0, 1.0) } else { vec4(1.0, 1.0, 1.0, 1.0) }
+}
@@ -0,0 +1,49 @@
+use std::f32::consts::TAU;
+use glm::{vec2, Vec2};
+use serde::{Deserialize, Serialize};
+use crate::open_gl::shaders::StandardShader;
+
+// Keep in sync with the symmetry guides in PIXEL_SHADER
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SymmetryMode {
+    Off,
+    Vertical,
+    Horizontal,
+    Radial
+}
+
+pub struct Symmetry {
+    pub mode: SymmetryMode,
+    pub center: Vec2,
+    pub folds: i32,
+    // The next click on the canvas moves the center
+    pub placing: bool
+}
+
+impl Symmetry {
+    pub fn new(center: Vec2) -> Self {
+        Symmetry { mode: SymmetryMode::Off, center, folds: 6, placing: false }
+    }
+
+    // Every copy of the point, starting with the point itself
+    pub fn apply(&self, p: Vec2) -> Vec<Vec2> {
+        let c = self.center;
+        match self.mode {
+            SymmetryMode::Off => vec![p],
+            SymmetryMode::Vertical => vec![p, vec2(2.0 * c.x - p.x, p.y)],
+            SymmetryMode::Horizontal => vec![p, vec2(p.x, 2.0 * c.y - p.y)],
+            SymmetryMode::Radial => (0..self.folds.max(1)).map(|i| {
+                let (s, cos) = (i as f32 * TAU / self.folds.max(1) as f32).sin_cos();
+                let d = p - c;
+                c + vec2(cos * d.x - s * d.y, s * d.x + cos * d.y)
+            }).collect()
+        }
+    }
+
+    pub fn bind(&self, shader: &StandardShader) {
+        shader.set_int(self.mode as i32, "symmetry_mode");
+        shader.set_vector2(&self.center, "symmetry_center");
+        shader.set_int(self.folds.max(1), "symmetry_folds");
+    }
+}
@@ -0,0 +1,315 @@
+use std::cell::{Ref, RefCell};
+use std::collections::HashMap;
+use glm::*;
+use crate::open_gl::shaders::ComputeShader;
+use crate::open_gl::texture::Texture2D;
+
+// Pages are square tiles of this size that only exist where something was drawn, undo steps keep the tiles they changed
+pub const TILE_SIZE: u32 = 256;
+
+// Tile coordinates, tile (0, 0) starts at canvas texel (0, 0)
+pub type TileKey = (i32, i32);
+
+// The part of an area that lies on one texture, in canvas texels
+pub struct Region<'a> {
+    pub texture: &'a Texture2D,
+    // Where the texture's bottom left corner is
+    pub origin: Vec2,
+    pub min: Vec2,
+    pub max: Vec2
+}
+
+impl Region<'_> {
+    // Runs the shader on every texel of the region with the texture as its image. Groups are 10 by 10, so it gets
+    // offset, the region's first texel, and end, the first one past it, plus origin to turn texels into image coordinates
+    pub fn dispatch(&self, shader: &ComputeShader) {
+        shader.set_ivector2(&self.min, "offset");
+        shader.set_ivector2(&self.max, "end");
+        shader.set_ivector2(&self.origin, "origin");
+        let size = self.max - self.min;
+
+        self.texture.bind_image(gl::TEXTURE0);
+        shader.bind();
+        shader.dispatch((size.x / 10.0).ceil() as u32, (size.y / 10.0).ceil() as u32, 1);
+        shader.wait();
+        shader.unbind();
+        self.texture.unbind_image(gl::TEXTURE0);
+    }
+}
+
+// Something to draw on in canvas texels, a texture that starts at texel (0, 0) or the tiles of a page
+pub trait Surface {
+    // Calls draw for every texture under the area from min_corner to max_corner. Tiles that don't exist yet are only
+    // made for ink, erasing where nothing was drawn has nothing to do
+    fn regions(&self, min_corner: Vec2, max_corner: Vec2, ink: bool, draw: &mut dyn FnMut(Region));
+    // Tightly packed RGBA with the bottom row first, transparent wherever nothing was drawn
+    fn read(&self, x: i32, y: i32, width: u32, height: u32) -> Vec<f32>;
+    fn write(&self, x: i32, y: i32, width: u32, height: u32, pixels: &[f32]);
+    fn clear(&self);
+}
+
+// Whatever falls off the texture is left out, and reads as transparent
+impl Surface for Texture2D {
+    fn regions(&self, min_corner: Vec2, max_corner: Vec2, _ink: bool, draw: &mut dyn FnMut(Region)) {
+        let size = vec2(self.get_width() as f32, self.get_height() as f32);
+        let min_corner = clamp(floor(min_corner), vec2(0.0, 0.0), size);
+        let max_corner = clamp(ceil(max_corner), vec2(0.0, 0.0), size);
+        if min_corner.x < max_corner.x && min_corner.y < max_corner.y {
+            draw(Region { texture: self, origin: vec2(0.0, 0.0), min: min_corner, max: max_corner });
+        }
+    }
+
+    fn read(&self, x: i32, y: i32, width: u32, height: u32) -> Vec<f32> {
+        let mut pixels = vec![0.0; (width * height * 4) as usize];
+        if let Some((x0, y0, x1, y1)) = overlap((x, y, width, height), (0, 0, self.get_width(), self.get_height())) {
+            let part = self.read_pixels(x0 as u32, y0 as u32, (x1 - x0) as u32, (y1 - y0) as u32);
+            copy_rect(&part, (x0, y0, (x1 - x0) as u32), &mut pixels, (x, y, width), (x0, y0, x1, y1));
+        }
+        pixels
+    }
+
+    fn write(&self, x: i32, y: i32, width: u32, height: u32, pixels: &[f32]) {
+        if let Some((x0, y0, x1, y1)) = overlap((x, y, width, height), (0, 0, self.get_width(), self.get_height())) {
+            let mut part = vec![0.0; ((x1 - x0) * (y1 - y0) * 4) as usize];
+            copy_rect(pixels, (x, y, width), &mut part, (x0, y0, (x1 - x0) as u32), (x0, y0, x1, y1));
+            self.write_pixels(x0 as u32, y0 as u32, (x1 - x0) as u32, (y1 - y0) as u32, &part);
+        }
+    }
+
+    fn clear(&self) {
+        Texture2D::clear(self);
+    }
+}
+
+// A canvas without edges, kept as tiles that are made the first time ink lands on them
+pub struct Tiles {
+    tiles: RefCell<HashMap<TileKey, Texture2D>>,
+    // For a history: what each tile written to since take_changes held before, None where it didn't exist
+    before: Option<RefCell<HashMap<TileKey, Option<Texture2D>>>>
+}
+
+impl Tiles {
+    pub fn new() -> Self {
+        Tiles { tiles: RefCell::new(HashMap::new()), before: None }
+    }
+
+    // From now on tiles are copied before they're first written to, see take_changes
+    pub(crate) fn track_changes(&mut self) {
+        self.before.get_or_insert_with(|| RefCell::new(HashMap::new()));
+    }
+
+    // The tiles written to since the last call with what they held before, and forgets them
+    pub(crate) fn take_changes(&self) -> Vec<(TileKey, Option<Texture2D>)> {
+        self.before.as_ref().map_or_else(Vec::new, |before| before.borrow_mut().drain().collect())
+    }
+
+    // Puts the pixels in as the tile, or removes it for None, and returns what was there. Isn't taken for a change
+    pub fn replace(&self, key: TileKey, pixels: Option<Texture2D>) -> Option<Texture2D> {
+        let mut tiles = self.tiles.borrow_mut();
+        match pixels {
+            Some(pixels) => tiles.insert(key, pixels),
+            None => tiles.remove(&key)
+        }
+    }
+
+    pub fn get(&self, key: TileKey) -> Option<Ref<'_, Texture2D>> {
+        Ref::filter_map(self.tiles.borrow(), |tiles| tiles.get(&key)).ok()
+    }
+
+    pub fn keys(&self) -> Vec<TileKey> {
+        self.tiles.borrow().keys().copied().collect()
+    }
+
+    // The corners of the smallest area that holds every tile, None without any
+    pub fn bounds(&self) -> Option<(Vec2, Vec2)> {
+        let tiles = self.tiles.borrow();
+        let first = *tiles.keys().next()?;
+        let (min_key, max_key) = tiles.keys().fold((first, first), |(min_key, max_key), key| {
+            ((Ord::min(min_key.0, key.0), Ord::min(min_key.1, key.1)), (Ord::max(max_key.0, key.0), Ord::max(max_key.1, key.1)))
+        });
+        Some((tile_origin(min_key), tile_origin((max_key.0 + 1, max_key.1 + 1))))
+    }
+
+    // A copy that doesn't keep track of changes
+    pub fn duplicate(&self) -> Self {
+        let tiles = self.tiles.borrow().iter().map(|(key, tile)| (*key, tile.clone())).collect();
+        Tiles { tiles: RefCell::new(tiles), before: None }
+    }
+
+    // Draws the tiles under a screen sized target, with the same mapping from screen pixels to canvas texels as the screen pass.
+    // Blending puts them over what the target already has instead of replacing it
+    pub fn render(&self, tile_shader: &ComputeShader, target: &Texture2D, view_offset: Vec2, view_zoom: f32, blend: bool) {
+        let screen_size = vec2(target.get_width() as f32, target.get_height() as f32);
+        tile_shader.set_vector2(&view_offset, "view_offset");
+        tile_shader.set_float(view_zoom, "view_zoom");
+        tile_shader.set_int(blend as i32, "blend");
+        tile_shader.set_int(1, "tile");
+
+        for (key, tile) in self.tiles.borrow().iter() {
+            let origin = tile_origin(*key);
+            let min_corner = clamp(floor((origin - view_offset) * view_zoom), vec2(0.0, 0.0), screen_size);
+            let max_corner = clamp(ceil((origin + vec2(TILE_SIZE as f32, TILE_SIZE as f32) - view_offset) * view_zoom), vec2(0.0, 0.0), screen_size);
+            if min_corner.x >= max_corner.x || min_corner.y >= max_corner.y {
+                continue;
+            }
+            tile_shader.set_vector2(&origin, "tile_origin");
+            tile.bind(gl::TEXTURE1);
+            Region { texture: target, origin: vec2(0.0, 0.0), min: min_corner, max: max_corner }.dispatch(tile_shader);
+            tile.unbind(gl::TEXTURE1);
+        }
+    }
+
+    // Makes sure a tile that's about to be written to exists, unless it doesn't and only ink may make it.
+    // Returns whether it's there to write to
+    fn touch(&self, key: TileKey, ink: bool) -> bool {
+        let mut tiles = self.tiles.borrow_mut();
+        let exists = tiles.contains_key(&key);
+        if !exists && !ink {
+            return false;
+        }
+        if let Some(before) = &self.before {
+            before.borrow_mut().entry(key).or_insert_with(|| tiles.get(&key).map(|tile| tile.clone()));
+        }
+        if !exists {
+            let tile = Texture2D::new(TILE_SIZE, TILE_SIZE);
+            tile.clear();
+            tiles.insert(key, tile);
+        }
+        true
+    }
+}
+
+impl Default for Tiles {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Surface for Tiles {
+    fn regions(&self, min_corner: Vec2, max_corner: Vec2, ink: bool, draw: &mut dyn FnMut(Region)) {
+        let (min_corner, max_corner) = (floor(min_corner), ceil(max_corner));
+        if min_corner.x >= max_corner.x || min_corner.y >= max_corner.y {
+            return;
+        }
+        let area = (min_corner.x as i32, min_corner.y as i32, (max_corner.x - min_corner.x) as u32, (max_corner.y - min_corner.y) as u32);
+        // Without ink only the tiles that exist matter, there may be far fewer of them than the area covers
+        let keys = if ink { covering(area) } else { self.keys().into_iter().filter(|key| overlap(area, tile_rect(*key)).is_some()).collect() };
+        let keys: Vec<TileKey> = keys.into_iter().filter(|key| self.touch(*key, ink)).collect();
+
+        let tiles = self.tiles.borrow();
+        for key in keys {
+            let origin = tile_origin(key);
+            let tile_end = origin + vec2(TILE_SIZE as f32, TILE_SIZE as f32);
+            draw(Region { texture: &tiles[&key], origin, min: max(min_corner, origin), max: min(max_corner, tile_end) });
+        }
+    }
+
+    fn read(&self, x: i32, y: i32, width: u32, height: u32) -> Vec<f32> {
+        let mut pixels = vec![0.0; (width * height * 4) as usize];
+        let tiles = self.tiles.borrow();
+        for key in covering((x, y, width, height)) {
+            let (Some(tile), Some((x0, y0, x1, y1))) = (tiles.get(&key), overlap((x, y, width, height), tile_rect(key))) else {
+                continue;
+            };
+            let (tile_x, tile_y) = (key.0 * TILE_SIZE as i32, key.1 * TILE_SIZE as i32);
+            let part = tile.read_pixels((x0 - tile_x) as u32, (y0 - tile_y) as u32, (x1 - x0) as u32, (y1 - y0) as u32);
+            copy_rect(&part, (x0, y0, (x1 - x0) as u32), &mut pixels, (x, y, width), (x0, y0, x1, y1));
+        }
+        pixels
+    }
+
+    fn write(&self, x: i32, y: i32, width: u32, height: u32, pixels: &[f32]) {
+        for key in covering((x, y, width, height)) {
+            let Some((x0, y0, x1, y1)) = overlap((x, y, width, height), tile_rect(key)) else {
+                continue;
+            };
+            let mut part = vec![0.0; ((x1 - x0) * (y1 - y0) * 4) as usize];
+            copy_rect(pixels, (x, y, width), &mut part, (x0, y0, (x1 - x0) as u32), (x0, y0, x1, y1));
+            if !self.touch(key, part.chunks_exact(4).any(|pixel| pixel[3] > 0.0)) {
+                continue;
+            }
+            let (tile_x, tile_y) = (key.0 * TILE_SIZE as i32, key.1 * TILE_SIZE as i32);
+            self.tiles.borrow()[&key].write_pixels((x0 - tile_x) as u32, (y0 - tile_y) as u32, (x1 - x0) as u32, (y1 - y0) as u32, &part);
+        }
+    }
+
+    // The tiles go, into the changes for a history
+    fn clear(&self) {
+        let tiles = std::mem::take(&mut *self.tiles.borrow_mut());
+        if let Some(before) = &self.before {
+            let mut before = before.borrow_mut();
+            for (key, tile) in tiles {
+                before.entry(key).or_insert(Some(tile));
+            }
+        }
+    }
+}
+
+// The canvas texel at a tile's bottom left corner
+pub fn tile_origin(key: TileKey) -> Vec2 {
+    vec2((key.0 * TILE_SIZE as i32) as f32, (key.1 * TILE_SIZE as i32) as f32)
+}
+
+fn tile_rect(key: TileKey) -> (i32, i32, u32, u32) {
+    (key.0 * TILE_SIZE as i32, key.1 * TILE_SIZE as i32, TILE_SIZE, TILE_SIZE)
+}
+
+// Every tile a rectangle of x, y, width and height touches
+fn covering((x, y, width, height): (i32, i32, u32, u32)) -> Vec<TileKey> {
+    if width == 0 || height == 0 {
+        return Vec::new();
+    }
+    let size = TILE_SIZE as i32;
+    let (columns, rows) = (x.div_euclid(size)..=(x + width as i32 - 1).div_euclid(size), y.div_euclid(size)..=(y + height as i32 - 1).div_euclid(size));
+    rows.flat_map(|row| columns.clone().map(move |column| (column, row))).collect()
+}
+
+// Where two rectangles of x, y, width and height overlap, as min and max (exclusive) corner
+fn overlap(a: (i32, i32, u32, u32), b: (i32, i32, u32, u32)) -> Option<(i32, i32, i32, i32)> {
+    let (x0, y0) = (Ord::max(a.0, b.0), Ord::max(a.1, b.1));
+    let (x1, y1) = (Ord::min(a.0 + a.2 as i32, b.0 + b.2 as i32), Ord::min(a.1 + a.3 as i32, b.1 + b.3 as i32));
+    (x0 < x1 && y0 < y1).then_some((x0, y0, x1, y1))
+}
+
+// Copies the pixels inside the rectangle from one tightly packed RGBA buffer to another,
+// each given with the texel of its first pixel and its width
+fn copy_rect(source: &[f32], (source_x, source_y, source_width): (i32, i32, u32), target: &mut [f32], (target_x, target_y, target_width): (i32, i32, u32), (x0, y0, x1, y1): (i32, i32, i32, i32)) {
+    let length = (x1 - x0) as usize * 4;
+    for y in y0..y1 {
+        let from = ((y - source_y) as usize * source_width as usize + (x0 - source_x) as usize) * 4;
+        let to = ((y - target_y) as usize * target_width as usize + (x0 - target_x) as usize) * 4;
+        target[to..to + length].copy_from_slice(&source[from..from + length]);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn covers_tiles_left_of_and_below_the_origin() {
+        assert_eq!(covering((-1, 0, 2, 1)), vec![(-1, 0), (0, 0)]);
+        assert_eq!(covering((-256, -257, 256, 2)), vec![(-1, -2), (-1, -1)]);
+        assert_eq!(covering((255, 255, 1, 1)), vec![(0, 0)]);
+        assert!(covering((10, 10, 0, 5)).is_empty());
+    }
+
+    #[test]
+    fn overlaps_only_where_both_rectangles_are() {
+        assert_eq!(overlap((-10, -10, 20, 20), tile_rect((0, 0))), Some((0, 0, 10, 10)));
+        assert_eq!(overlap((-10, -10, 20, 20), tile_rect((-1, 0))), Some((-10, 0, 0, 10)));
+        assert_eq!(overlap((0, 0, 256, 256), tile_rect((1, 0))), None);
+    }
+
+    #[test]
+    fn copies_the_rectangle_between_buffers() {
+        // 3x2 source starting at texel (-1, 5), the right column goes into a 2x2 target starting at (1, 5)
+        let source: Vec<f32> = (0..24).map(|i| i as f32).collect();
+        let mut target = vec![-1.0; 16];
+        copy_rect(&source, (-1, 5, 3), &mut target, (1, 5, 2), (1, 5, 2, 7));
+        assert_eq!(&target[0..4], &source[8..12]);
+        assert_eq!(&target[8..12], &source[20..24]);
+        assert_eq!(&target[4..8], &[-1.0; 4]);
+        assert_eq!(&target[12..16], &[-1.0; 4]);
+    }
+}
@@ -0,0 +1,110 @@
+use glm::*;
+use serde::{Deserialize, Serialize};
+use crate::background::Background;
+use crate::callout::Callout;
+use crate::eyedropper::Eyedropper;
+use crate::fill::Bucket;
+use crate::gradient::Gradient;
+use crate::guides::{Grid, Ruler};
+use crate::laser::Laser;
+use crate::measure::Measure;
+use crate::redact::Redactor;
+use crate::shapes::Recognizer;
+use crate::spotlight::Spotlight;
+use crate::stamp::Stamps;
+use crate::symmetry::Symmetry;
+use crate::tools::ToolRegistry;
+
+// Which kind of tool the buttons go to. Brush hands them to the tool registry and whatever tool it has selected,
+// the others are still handled by the front end itself
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ToolKind {
+    Brush,
+    RectangleSelect,
+    LassoSelect,
+    Fill,
+    Eyedropper,
+    Redact,
+    Gradient,
+    Measure,
+    Stamp,
+    Callout
+}
+
+impl ToolKind {
+    pub const ALL: [ToolKind; 10] = [ToolKind::Brush, ToolKind::RectangleSelect, ToolKind::LassoSelect, ToolKind::Fill, ToolKind::Eyedropper, ToolKind::Redact, ToolKind::Gradient, ToolKind::Measure, ToolKind::Stamp, ToolKind::Callout];
+
+    // Name used in config files
+    pub fn name(&self) -> &'static str {
+        match self {
+            ToolKind::Brush => "brush",
+            ToolKind::RectangleSelect => "rectangle_select",
+            ToolKind::LassoSelect => "lasso_select",
+            ToolKind::Fill => "fill",
+            ToolKind::Eyedropper => "eyedropper",
+            ToolKind::Redact => "redact",
+            ToolKind::Gradient => "gradient",
+            ToolKind::Measure => "measure",
+            ToolKind::Stamp => "stamp",
+            ToolKind::Callout => "callout"
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<ToolKind> {
+        ToolKind::ALL.into_iter().find(|tool| tool.name() == name)
+    }
+
+    pub fn is_selection(&self) -> bool {
+        matches!(self, ToolKind::RectangleSelect | ToolKind::LassoSelect)
+    }
+}
+
+// Everything the tools and guides keep between frames, what the panels edit and the settings are made of
+pub struct Toolbox {
+    pub kind: ToolKind,
+    pub registry: ToolRegistry,
+    pub color: Vec4,
+    pub brush_size: i32,
+    pub recognizer: Recognizer,
+    pub laser: Laser,
+    pub spotlight: Spotlight,
+    pub bucket: Bucket,
+    pub eyedropper: Eyedropper,
+    pub redactor: Redactor,
+    pub gradient: Gradient,
+    pub measure: Measure,
+    pub stamps: Stamps,
+    pub callout: Callout,
+    pub symmetry: Symmetry,
+    pub grid: Grid,
+    pub ruler: Ruler,
+    pub background: Background
+}
+
+impl Toolbox {
+    // The laser draws at screen size, the symmetry center and the ruler start out in the middle of the screen
+    pub fn new(screen_width: u32, screen_height: u32) -> Self {
+        let center = vec2(screen_width as f32, screen_height as f32) * 0.5;
+        Toolbox {
+            kind: ToolKind::Brush,
+            registry: ToolRegistry::new(),
+            color: vec4(1.0, 1.0, 1.0, 1.0),
+            brush_size: 5,
+            recognizer: Recognizer::new(),
+            laser: Laser::new(screen_width, screen_height),
+            spotlight: Spotlight::new(),
+            bucket: Bucket::new(),
+            eyedropper: Eyedropper::new(),
+            redactor: Redactor::new(),
+            gradient: Gradient::new(),
+            measure: Measure::new(),
+            stamps: Stamps::new(),
+            callout: Callout::new(),
+            symmetry: Symmetry::new(center),
+            grid: Grid::new(),
+            ruler: Ruler::new(center),
+            background: Background::new()
+        }
+    }
+}
@@ -0,0 +1,433 @@
+use glm::*;
+use imgui_glfw_rs::imgui::Ui;
+use crate::brush::{paint, MAX_BRUSH_SIZE};
+use crate::history::History;
+use crate::open_gl::shaders::ComputeShader;
+use crate::shapes::Recognizer;
+use crate::symmetry::Symmetry;
+use crate::tiles::Surface;
+
+// Plugins built against another version of this interface are refused
+pub const PLUGIN_API_VERSION: u32 = 2;
+// Compiler, features and a hash of this crate's source, see build.rs. Plugins built any other way are refused too
+pub const PLUGIN_BUILD: &str = env!("OVERDRAW_BUILD");
+pub const PRIMARY: usize = 1;
+pub const SECONDARY: usize = 2;
+
+// Whatever the front end draws on top of the canvas that strokes should follow, like a ruler and a grid
+pub trait Guides {
+    // Called once per press, before the tool sees it
+    fn begin_stroke(&mut self, p: Vec2);
+    // Pulls a point onto the guide the stroke started at, if any
+    fn guide(&self, p: Vec2) -> Vec2;
+    fn snap(&self, p: Vec2) -> Vec2;
+}
+
+// What a tool did to the history, recorded so it can be done again on another canvas, like a collaborator's
+#[derive(Clone, Copy, Debug)]
+pub enum StrokeEvent {
+    // A new undo step on top of the current one
+    Begin,
+    Paint { color: Vec4, from: Vec2, to: Vec2, size: i32 },
+    // Back to how the undo step started
+    Revert,
+    Clear
+}
+
+impl StrokeEvent {
+    pub fn apply(&self, history: &mut History, compute_shader: &ComputeShader) {
+        match *self {
+            StrokeEvent::Begin => {
+                history.begin_step();
+            }
+            StrokeEvent::Paint { color, from, to, size } => paint(compute_shader, history.get_current(), color, to, from, size),
+            StrokeEvent::Revert => history.revert(),
+            StrokeEvent::Clear => history.get_current().clear()
+        }
+    }
+}
+
+// Everything a tool may touch for one event, cursors are canvas texels except for screen_cursor
+pub struct ToolContext<'a> {
+    pub history: &'a mut History,
+    pub compute_shader: &'a ComputeShader,
+    pub guides: &'a mut dyn Guides,
+    pub symmetry: &'a Symmetry,
+    pub recognizer: &'a mut Recognizer,
+    pub color: &'a mut Vec4,
+    pub brush_size: &'a mut i32,
+    pub cursor: Vec2,
+    pub prev_cursor: Vec2,
+    // Window pixels with the origin at the top left, for gestures that measure how far the mouse moved
+    pub screen_cursor: Vec2,
+    // The straight line modifier is held, LShift unless rebound
+    pub straight_line: bool,
+    // Everything the tool did to the history during this event, in order
+    pub events: &'a mut Vec<StrokeEvent>
+}
+
+// Tools change the history through these instead of painting on it directly, so their strokes can be replayed
+impl ToolContext<'_> {
+    pub fn begin(&mut self) {
+        self.record(StrokeEvent::Begin);
+    }
+
+    pub fn paint(&mut self, color: Vec4, from: Vec2, to: Vec2, size: i32) {
+        self.record(StrokeEvent::Paint { color, from, to, size });
+    }
+
+    // Paints the segment and all of its mirrored copies
+    pub fn paint_symmetric(&mut self, color: Vec4, from: Vec2, to: Vec2, size: i32) {
+        for (from, to) in self.symmetry.apply(from).into_iter().zip(self.symmetry.apply(to)) {
+            self.paint(color, from, to, size);
+        }
+    }
+
+    pub fn paint_polyline_symmetric(&mut self, color: Vec4, points: &[Vec2], size: i32) {
+        for segment in points.windows(2) {
+            self.paint_symmetric(color, segment[0], segment[1], size);
+        }
+    }
+
+    pub fn revert(&mut self) {
+        self.record(StrokeEvent::Revert);
+    }
+
+    pub fn clear(&mut self) {
+        self.record(StrokeEvent::Clear);
+    }
+
+    fn record(&mut self, event: StrokeEvent) {
+        event.apply(self.history, self.compute_shader);
+        self.events.push(event);
+    }
+}
+
+// A tool gets a press, drags every frame the button stays down and a release, always for the same button
+pub trait Tool {
+    // Shown in the tool list and used to pick the tool from settings
+    fn name(&self) -> &str;
+
+    fn press(&mut self, _button: usize, _context: &mut ToolContext) {}
+
+    fn drag(&mut self, _button: usize, _context: &mut ToolContext) {}
+
+    fn release(&mut self, _button: usize, _context: &mut ToolContext) {}
+
+    // Drawn into the overlay, which is cleared every frame
+    fn preview(&mut self, _context: &mut ToolContext, _overlay: &dyn Surface) {}
+
+    // Where the brush outline is drawn instead of the cursor
+    fn anchor(&self) -> Option<Vec2> {
+        None
+    }
+
+    // Goes into the Tools window while the tool is selected. Not called for tools from plugins
+    fn settings(&mut self, _ui: &Ui) {}
+}
+
+// Freehand strokes that snap to shapes, straight lines with the modifier, and the same without shapes for erasing
+pub struct Brush {
+    erase: bool,
+    line_start: Option<Vec2>
+}
+
+impl Brush {
+    pub fn new() -> Self {
+        Brush { erase: false, line_start: None }
+    }
+
+    pub fn eraser() -> Self {
+        Brush { erase: true, line_start: None }
+    }
+
+    fn color(&self, context: &ToolContext) -> Vec4 {
+        if self.erase { vec4(0.0, 0.0, 0.0, 0.0) } else { *context.color }
+    }
+}
+
+impl Default for Brush {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Tool for Brush {
+    fn name(&self) -> &str {
+        if self.erase { "eraser" } else { "brush" }
+    }
+
+    fn press(&mut self, _button: usize, context: &mut ToolContext) {
+        context.begin();
+        context.recognizer.begin(context.cursor);
+        self.line_start = Some(context.guides.snap(context.guides.guide(context.cursor)));
+    }
+
+    fn drag(&mut self, _button: usize, context: &mut ToolContext) {
+        let color = self.color(context);
+        let cursor = context.guides.guide(context.cursor);
+        if context.straight_line {
+            // Straight line from where the stroke started
+            if let Some(start) = self.line_start {
+                context.revert();
+                let end = context.guides.snap(cursor);
+                context.paint_polyline_symmetric(color, &[start, end], *context.brush_size);
+            }
+        } else if self.erase {
+            let prev_cursor = context.guides.guide(context.prev_cursor);
+            context.paint_symmetric(color, prev_cursor, cursor, *context.brush_size);
+        } else if !context.recognizer.is_snapped() {
+            let prev_cursor = context.guides.guide(context.prev_cursor);
+            context.paint_symmetric(color, prev_cursor, cursor, *context.brush_size);
+
+            context.recognizer.add_point(cursor);
+            if let Some(shape) = context.recognizer.poll() {
+                context.revert();
+                let shape = shape.map_points(|p| context.guides.snap(p));
+                context.paint_polyline_symmetric(color, &shape.outline(), *context.brush_size);
+            }
+        }
+    }
+
+    fn release(&mut self, _button: usize, _context: &mut ToolContext) {
+        self.line_start = None;
+    }
+}
+
+// Dragging sideways sets the size from where the press was, the other button clears the page
+pub struct ResizeGesture {
+    start: Option<(Vec2, Vec2, i32)>
+}
+
+impl ResizeGesture {
+    pub fn new() -> Self {
+        ResizeGesture { start: None }
+    }
+}
+
+impl Default for ResizeGesture {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Tool for ResizeGesture {
+    fn name(&self) -> &str {
+        "resize"
+    }
+
+    fn press(&mut self, button: usize, context: &mut ToolContext) {
+        if button == PRIMARY {
+            self.start = Some((context.screen_cursor, context.cursor, *context.brush_size));
+        } else {
+            context.begin();
+            context.clear();
+        }
+    }
+
+    fn drag(&mut self, _button: usize, context: &mut ToolContext) {
+        if let Some((screen_start, _, size)) = self.start {
+            *context.brush_size = Ord::min((context.screen_cursor.x + size as f32 - screen_start.x).abs() as i32, MAX_BRUSH_SIZE);
+        }
+    }
+
+    fn release(&mut self, _button: usize, _context: &mut ToolContext) {
+        self.start = None;
+    }
+
+    fn anchor(&self) -> Option<Vec2> {
+        self.start.map(|(_, cursor, _)| cursor)
+    }
+}
+
+// Which tool gets the buttons: the gesture while its modifier is held, the eraser for the secondary button,
+// otherwise the selected one. Plugins add selectable tools
+pub struct ToolRegistry {
+    // Declared before the libraries, the tools they contain have to be dropped first
+    tools: Vec<Box<dyn Tool>>,
+    selected: usize,
+    eraser: usize,
+    gesture: usize,
+    // The tool and the button it got the press for. Latched here instead of relying on the press edge,
+    // which gets lost when the click lands on the frame the GUI lets go of the mouse
+    active: Option<(usize, usize)>,
+    // Indices of the tools that came from plugins
+    #[cfg(feature = "dynamic-tools")]
+    plugin_tools: Vec<usize>,
+    #[cfg(feature = "dynamic-tools")]
+    libraries: Vec<libloading::Library>
+}
+
+impl ToolRegistry {
+    pub fn new() -> Self {
+        let mut registry = ToolRegistry {
+            tools: vec![Box::new(Brush::new()), Box::new(Brush::eraser()), Box::new(ResizeGesture::new())],
+            selected: 0,
+            eraser: 1,
+            gesture: 2,
+            active: None,
+            #[cfg(feature = "dynamic-tools")]
+            plugin_tools: Vec::new(),
+            #[cfg(feature = "dynamic-tools")]
+            libraries: Vec::new()
+        };
+        register_compiled_tools(&mut registry);
+        registry
+    }
+
+    // Adds a tool to the ones that can be selected, a tool with a name that's taken is refused
+    pub fn register(&mut self, tool: Box<dyn Tool>) -> Result<(), String> {
+        if self.tools.iter().any(|existing| existing.name() == tool.name()) {
+            return Err(format!("there already is a tool called '{}'", tool.name()));
+        }
+        self.tools.push(tool);
+        Ok(())
+    }
+
+    // Index and name of every tool that can be selected, in the order they were registered
+    pub fn selectable(&self) -> Vec<(usize, String)> {
+        self.tools.iter().enumerate()
+            .filter(|(i, _)| *i != self.eraser && *i != self.gesture)
+            .map(|(i, tool)| (i, tool.name().to_string()))
+            .collect()
+    }
+
+    pub fn get_selected(&self) -> usize {
+        self.selected
+    }
+
+    // Takes effect with the next press, a stroke that's going keeps its tool
+    pub fn select(&mut self, index: usize) {
+        if index < self.tools.len() && index != self.eraser && index != self.gesture {
+            self.selected = index;
+        }
+    }
+
+    pub fn select_name(&mut self, name: &str) -> bool {
+        match self.tools.iter().position(|tool| tool.name() == name) {
+            Some(index) if index != self.eraser && index != self.gesture => {
+                self.selected = index;
+                true
+            }
+            _ => false
+        }
+    }
+
+    pub fn selected_name(&self) -> &str {
+        self.tools[self.selected].name()
+    }
+
+    // The tool that has the buttons, or the one that would get the next press
+    pub fn active_name(&self) -> &str {
+        self.tools[self.active.map_or(self.selected, |(tool, _)| tool)].name()
+    }
+
+    // Whether some tool has the buttons, a stroke is going until it lets go of them
+    pub fn is_active(&self) -> bool {
+        self.active.is_some()
+    }
+
+    // Whether the resize gesture has the buttons, or would get the next press
+    pub fn is_gesture(&self, gesture_held: bool) -> bool {
+        match self.active {
+            Some((tool, _)) => tool == self.gesture,
+            None => gesture_held
+        }
+    }
+
+    // Turns the button states of this frame into press, drag and release calls
+    pub fn update(&mut self, primary: bool, secondary: bool, gesture_held: bool, context: &mut ToolContext) {
+        let held = if primary { Some(PRIMARY) } else if secondary { Some(SECONDARY) } else { None };
+        match (self.active, held) {
+            (Some((tool, button)), _) if !((button == PRIMARY && primary) || (button == SECONDARY && secondary)) => {
+                self.tools[tool].release(button, context);
+                self.active = None;
+            }
+            (Some((tool, button)), _) => self.tools[tool].drag(button, context),
+            (None, Some(button)) => {
+                let tool = if gesture_held { self.gesture } else if button == SECONDARY { self.eraser } else { self.selected };
+                context.guides.begin_stroke(context.cursor);
+                self.tools[tool].press(button, context);
+                self.active = Some((tool, button));
+            }
+            (None, None) => {}
+        }
+    }
+
+    pub fn preview(&mut self, context: &mut ToolContext, overlay: &dyn Surface) {
+        let tool = self.active.map_or(self.selected, |(tool, _)| tool);
+        self.tools[tool].preview(context, overlay);
+    }
+
+    pub fn anchor(&self) -> Option<Vec2> {
+        self.active.and_then(|(tool, _)| self.tools[tool].anchor())
+    }
+
+    pub fn settings(&mut self, ui: &Ui) {
+        #[cfg(feature = "dynamic-tools")]
+        if self.plugin_tools.contains(&self.selected) {
+            return;
+        }
+        self.tools[self.selected].settings(ui);
+    }
+
+    // Loads every library in the directory that exports the plugin interface:
+    //     #[no_mangle] pub static OVERDRAW_PLUGIN_API: u32 = overdraw::tools::PLUGIN_API_VERSION;
+    //     #[no_mangle] pub static OVERDRAW_PLUGIN_BUILD: &str = overdraw::tools::PLUGIN_BUILD;
+    //     #[no_mangle] pub fn overdraw_register_tools(registry: &mut ToolRegistry, load_gl: &dyn Fn(&str) -> *const c_void)
+    // Plugins link their own copy of the gl crate, so they have to load its functions with load_gl before drawing.
+    // They link their own ImGui too, which never gets a context, so plugin tools can't draw any UI and their
+    // settings are never called. The interface is plain Rust, so the build has to match as well as the version
+    #[cfg(feature = "dynamic-tools")]
+    pub fn load_plugins(&mut self, dir: &std::path::Path, load_gl: &dyn Fn(&str) -> *const std::ffi::c_void) -> Vec<String> {
+        type Register = fn(&mut ToolRegistry, &dyn Fn(&str) -> *const std::ffi::c_void);
+
+        let Ok(entries) = std::fs::read_dir(dir) else {
+            return Vec::new();
+        };
+        let mut problems = Vec::new();
+        for path in entries.flatten().map(|entry| entry.path()) {
+            if path.extension().and_then(|extension| extension.to_str()) != Some(std::env::consts::DLL_EXTENSION) {
+                continue;
+            }
+            // Running the library's initialisers is what loading a plugin means, there's no way to check it first
+            let loaded = unsafe {
+                libloading::Library::new(&path).map_err(|error| error.to_string()).and_then(|library| {
+                    let version = **library.get::<*const u32>(b"OVERDRAW_PLUGIN_API").map_err(|error| error.to_string())?;
+                    if version != PLUGIN_API_VERSION {
+                        return Err(format!("built for plugin interface {}, this is {}", version, PLUGIN_API_VERSION));
+                    }
+                    let build = **library.get::<*const &str>(b"OVERDRAW_PLUGIN_BUILD").map_err(|error| error.to_string())?;
+                    if build != PLUGIN_BUILD {
+                        return Err(format!("built as {}, this is {}", build, PLUGIN_BUILD));
+                    }
+                    let register = *library.get::<Register>(b"overdraw_register_tools").map_err(|error| error.to_string())?;
+                    Ok((library, register))
+                })
+            };
+            match loaded {
+                Ok((library, register)) => {
+                    let first = self.tools.len();
+                    register(self, load_gl);
+                    self.plugin_tools.extend(first..self.tools.len());
+                    self.libraries.push(library);
+                }
+                Err(error) => problems.push(format!("{}: {}", path.display(), error))
+            }
+        }
+        problems
+    }
+}
+
+impl Default for ToolRegistry {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+// Tools that are part of the build, each behind its own feature
+#[allow(unused_variables)]
+fn register_compiled_tools(registry: &mut ToolRegistry) {
+    #[cfg(feature = "spray")]
+    let _ = registry.register(Box::new(crate::spray::Spray::new()));
+}
@@ -0,0 +1,76 @@
+use glm::*;
+use crate::input::Input;
+use crate::keybindings::{Action, Keybindings};
+use crate::open_gl::shaders::StandardShader;
+
+const MIN_ZOOM: f32 = 0.1;
+const MAX_ZOOM: f32 = 8.0;
+// Zoom factor per notch of the scroll wheel
+const ZOOM_STEP: f32 = 1.1;
+// Fills and gradients read back what's visible, zoomed far out that's capped to this many texels per side
+const MAX_AREA: f32 = 4096.0;
+
+// Maps screen coordinates to canvas texels, at the start one texel is one screen pixel with (0, 0) in the bottom left
+pub struct View {
+    offset: Vec2,
+    zoom: f32,
+    pan: Option<Vec2>
+}
+
+impl View {
+    pub fn new() -> Self {
+        View { offset: vec2(0.0, 0.0), zoom: 1.0, pan: None }
+    }
+
+    pub fn to_canvas(&self, screen: Vec2) -> Vec2 {
+        self.offset + screen / self.zoom
+    }
+
+    // Pans with the pan key + drag or the pan button and zooms around the cursor with the zoom key + scroll, returns true while it has the mouse
+    pub fn update(&mut self, input: &Input, keybindings: &Keybindings, screen_cursor: Vec2) -> bool {
+        if keybindings.held(Action::Zoom, input) && input.get_scroll().y != 0.0 {
+            let anchor = self.to_canvas(screen_cursor);
+            self.zoom = (self.zoom * ZOOM_STEP.powf(input.get_scroll().y)).clamp(MIN_ZOOM, MAX_ZOOM);
+            self.offset = anchor - screen_cursor / self.zoom;
+        }
+
+        let panning = input.get_button(3) || (keybindings.held(Action::PanHold, input) && input.get_button(1));
+        if !panning {
+            self.pan = None;
+            return false;
+        }
+        if let Some(prev) = self.pan {
+            self.offset = self.offset - (screen_cursor - prev) / self.zoom;
+        }
+        self.pan = Some(screen_cursor);
+        true
+    }
+
+    // Back to one screen pixel per texel with the canvas where it started
+    pub fn reset(&mut self) {
+        self.offset = vec2(0.0, 0.0);
+        self.zoom = 1.0;
+        self.pan = None;
+    }
+
+    pub fn offset(&self) -> Vec2 {
+        self.offset
+    }
+
+    pub fn zoom(&self) -> f32 {
+        self.zoom
+    }
+
+    // Corners of the texels on screen, for tools that need an edge on a canvas that has none
+    pub fn area(&self, screen_size: Vec2) -> (Vec2, Vec2) {
+        let (min_corner, max_corner) = (self.to_canvas(vec2(0.0, 0.0)), self.to_canvas(screen_size));
+        let center = (min_corner + max_corner) * 0.5;
+        let half = min(max_corner - min_corner, vec2(MAX_AREA, MAX_AREA)) * 0.5;
+        (floor(center - half), ceil(center + half))
+    }
+
+    pub fn bind(&self, shader: &StandardShader) {
+        shader.set_vector2(&self.offset, "view_offset");
+        shader.set_float(self.zoom, "view_zoom");
+    }
+}
@@ -1,22 +1,37 @@
 use std::sync::mpsc::Receiver;
-use device_query::Keycode;
 use glm::*;
 use imgui_glfw_rs::{glfw, ImguiGLFW};
 use crate::input::Input;
+use crate::keybindings::{Action, Keybindings};
 use imgui_glfw_rs::glfw::*;
 use imgui_glfw_rs::glfw::OpenGlProfileHint::Core;
 use crate::open_gl::viewport;
+use crate::passthrough::set_mouse_passthrough;
+use serde::{Deserialize, Serialize};
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum OverlayMode {
+    Hidden,
+    Edit,
+    // Visible and on top, but every click goes to the windows below
+    Presenting
+}
 
 pub struct Win {
-    edit: bool,
+    mode: OverlayMode,
+    content_scale: Vec2,
     pub p_glfw: Glfw,
     pub p_window: Window,
     pub events: Receiver<(f64, WindowEvent)>
 }
 
 impl Win {
-    pub fn new() -> Self {
-        let mut p_glfw = glfw::init(glfw::FAIL_ON_ERRORS).expect("[ERROR]: Failed to initialize Window!");
+    // Covers the work area of the given monitor, counted from 1 in the order the system lists them, or the primary one
+    pub fn new(monitor: Option<usize>) -> Result<Self, String> {
+        // Errors are printed instead of panicking, so a failed call below comes back as an error
+        let mut p_glfw = glfw::init(Some(glfw::Callback { f: print_error as fn(glfw::Error, String, &()), data: () }))
+            .map_err(|error| format!("can't initialize GLFW: {}", error))?;
 
         p_glfw.window_hint(WindowHint::Floating(true));
         p_glfw.window_hint(WindowHint::Decorated(false));
@@ -27,10 +42,24 @@ impl Win {
         p_glfw.window_hint(WindowHint::OpenGlProfile(Core));
         p_glfw.window_hint(WindowHint::OpenGlForwardCompat(true));
 
-        let (mut p_window, events) = p_glfw.with_primary_monitor(|glfw, monitor| {
-            let area = monitor.as_ref().unwrap().get_workarea();
-            glfw.create_window(area.2 as u32, area.3 as u32, "Overdraw", WindowMode::Windowed)
-        }).expect("[ERROR]: Failed to create Window!");
+        let area = match monitor {
+            Some(index) => p_glfw.with_connected_monitors(|_, monitors| {
+                match monitors.get(index.wrapping_sub(1)) {
+                    Some(monitor) => Ok(monitor.get_workarea()),
+                    None => {
+                        let names: Vec<String> = monitors.iter().enumerate()
+                            .map(|(i, monitor)| format!("{}: {}", i + 1, monitor.get_name().unwrap_or_default()))
+                            .collect();
+                        Err(format!("there is no monitor {}, connected monitors are {}", index, names.join(", ")))
+                    }
+                }
+            })?,
+            None => p_glfw.with_primary_monitor(|_, monitor| monitor.map(|monitor| monitor.get_workarea()))
+                .ok_or("no monitor found")?
+        };
+        let (mut p_window, events) = p_glfw.create_window(area.2 as u32, area.3 as u32, "Overdraw", WindowMode::Windowed)
+            .ok_or("can't create the window, it needs OpenGL 4.6")?;
+        p_window.set_pos(area.0, area.1);
 
         p_window.make_current();
         p_window.set_all_polling(true);
@@ -45,28 +74,56 @@ impl Win {
             gl::BlendFunc(gl::SRC_ALPHA, gl::ONE_MINUS_SRC_ALPHA);
         }
 
-        Win {
-            edit: false,
+        let content_scale = vec2(p_window.get_content_scale().0, p_window.get_content_scale().1);
+
+        Ok(Win {
+            mode: OverlayMode::Hidden,
+            content_scale,
             p_glfw,
             p_window,
             events
+        })
+    }
+
+    pub fn edit(&mut self, input: &Input, keybindings: &Keybindings) -> OverlayMode {
+        if keybindings.pressed(Action::ToggleEdit, input) {
+            if self.mode == OverlayMode::Hidden {
+                self.set_mode(OverlayMode::Edit);
+            } else {
+                self.set_mode(OverlayMode::Hidden);
+            }
+        }
+        if keybindings.pressed(Action::TogglePresenting, input) {
+            if self.mode == OverlayMode::Presenting {
+                self.set_mode(OverlayMode::Edit);
+            } else {
+                self.set_mode(OverlayMode::Presenting);
+            }
         }
+        self.mode
     }
 
-    pub fn edit(&mut self, input: &Input) -> bool {
-        if input.get_key(Keycode::LControl) && input.get_key(Keycode::LAlt){
-            if input.get_key_down(Keycode::A) {
-                if self.edit == false {
-                    self.show();
-                    self.focus();
-                    self.edit = true;
-                } else {
-                    self.hide();
-                    self.edit = false;
-                }
+    pub fn get_mode(&self) -> OverlayMode {
+        self.mode
+    }
+
+    pub fn set_mode(&mut self, mode: OverlayMode) {
+        if let Err(error) = set_mouse_passthrough(&self.p_glfw, &self.p_window, mode == OverlayMode::Presenting) {
+            eprintln!("[WARNING::WINDOW::PASSTHROUGH_FAILED] {}", error);
+            if mode == OverlayMode::Presenting {
+                return;
             }
         }
-        self.edit
+
+        match mode {
+            OverlayMode::Hidden => self.hide(),
+            OverlayMode::Edit => {
+                self.show();
+                self.focus();
+            }
+            OverlayMode::Presenting => self.show()
+        }
+        self.mode = mode;
     }
     pub fn get_pos_x(&self) -> u32 {
         unsafe {
@@ -96,6 +153,11 @@ impl Win {
         vec2(self.p_window.get_size().0 as f32, self.p_window.get_size().1 as f32)
     }
 
+    // Physical pixels per window coordinate
+    pub fn get_content_scale(&self) -> Vec2 {
+        self.content_scale
+    }
+
     pub fn open(&self) -> bool {
         !self.p_window.should_close()
     }
@@ -115,13 +177,13 @@ impl Win {
                 WindowEvent::MouseButton(_, _, _) => {}
                 WindowEvent::CursorPos(_, _) => {}
                 WindowEvent::CursorEnter(_) => {}
-                WindowEvent::Scroll(_, _) => {}
+                WindowEvent::Scroll(x, y) => input.add_scroll(x as f32, y as f32),
                 WindowEvent::Key(_, _, _, _) => {}
                 WindowEvent::Char(_) => {}
                 WindowEvent::CharModifiers(_, _) => {}
                 WindowEvent::FileDrop(_) => {}
                 WindowEvent::Maximize(_) => {}
-                WindowEvent::ContentScale(_, _) => {}
+                WindowEvent::ContentScale(x, y) => self.content_scale = vec2(x, y),
             }
         }
 
@@ -143,4 +205,8 @@ impl Win {
     pub fn swap_buffers(&mut self) {
         self.p_window.swap_buffers();
     }
-}
\ No newline at end of file
+}
+
+fn print_error(_: glfw::Error, description: String, _: &()) {
+    eprintln!("[ERROR::WINDOW::GLFW] {}", description);
+}